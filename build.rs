@@ -0,0 +1,7 @@
+fn main() {
+    // Reported by `stack --version --verbose`, only build scripts are told the target.
+    println!(
+        "cargo:rustc-env=TARGET={}",
+        std::env::var("TARGET").expect("TARGET is set by cargo.")
+    );
+}
@@ -0,0 +1,222 @@
+/// The two branches `stack up` pushes for a commit: the head branch at the commit and the base
+/// branch at its parent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BranchKind {
+    Head,
+    Base,
+}
+
+/// Name of the branch the pull request for `commit_id` is opened from.
+pub fn head_branch_name(prefix: &str, commit_id: &str, suffix: &str) -> String {
+    format!("{}{}{}", prefix, commit_id, suffix)
+}
+
+/// Name of the branch the pull request for `commit_id` is opened onto, when its parent has no pull
+/// request to stack it on.
+pub fn base_branch_name(prefix: &str, commit_id: &str, suffix: &str) -> String {
+    format!("{}{}{}", prefix, commit_id, suffix)
+}
+
+/// Turns `name`, like a user name, into something that can be used in a branch name: characters
+/// git doesn't allow or that would need quoting in a shell become dashes. `None` if nothing is
+/// left.
+pub fn sanitize_branch_component(name: &str) -> Option<String> {
+    let mut sanitized = String::new();
+    for c in name.chars() {
+        let c = if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+            c
+        } else {
+            '-'
+        };
+        // Repeated dashes are collapsed, and '..' isn't allowed in a ref.
+        match (sanitized.chars().last(), c) {
+            (Some('-'), '-') | (Some('.'), '.') => {}
+            _ => sanitized.push(c),
+        }
+    }
+    let sanitized = sanitized.trim_matches(|c| c == '-' || c == '.');
+    if sanitized.is_empty() {
+        None
+    } else {
+        Some(sanitized.to_string())
+    }
+}
+
+/// The longest slug `slugify` makes, so branch names stay readable.
+pub const MAX_SLUG_LENGTH: usize = 50;
+
+/// Turns a pull request title into a branch name component: lowercased, with runs of whitespace,
+/// dashes and underscores becoming a dash and everything else but ASCII letters and digits
+/// dropped. It's cut at a dash if that keeps it under `MAX_SLUG_LENGTH`. `None` if nothing is
+/// left.
+pub fn slugify(title: &str) -> Option<String> {
+    let mut slug = String::new();
+    for c in title.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if (c.is_whitespace() || c == '-' || c == '_') && !slug.is_empty()
+            && !slug.ends_with('-')
+        {
+            slug.push('-');
+        }
+    }
+    if slug.len() > MAX_SLUG_LENGTH {
+        let cut = match slug[..MAX_SLUG_LENGTH + 1].rfind('-') {
+            Some(dash) if dash > 0 => dash,
+            _ => MAX_SLUG_LENGTH,
+        };
+        slug.truncate(cut);
+    }
+    let slug = slug.trim_matches('-');
+    if slug.is_empty() {
+        None
+    } else {
+        Some(slug.to_string())
+    }
+}
+
+/// Reads the commit id and kind back from a branch named by `head_branch_name` or
+/// `base_branch_name`.
+pub fn parse_branch_name<'a>(
+    prefix: &str,
+    head_suffix: &str,
+    base_suffix: &str,
+    name: &'a str,
+) -> Option<(&'a str, BranchKind)> {
+    if !name.starts_with(prefix) {
+        return None;
+    }
+    let name = &name[prefix.len()..];
+    let (commit_id, kind) = if name.ends_with(head_suffix) {
+        (&name[..name.len() - head_suffix.len()], BranchKind::Head)
+    } else if name.ends_with(base_suffix) {
+        (&name[..name.len() - base_suffix.len()], BranchKind::Base)
+    } else {
+        return None;
+    };
+    if commit_id.is_empty() {
+        None
+    } else {
+        Some((commit_id, kind))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMMIT_ID: &str = "3e265444a803eac928b02a735564d0a544759215";
+
+    #[test]
+    fn parse_branch_name_can_parse_head_branch_name() {
+        let name = head_branch_name("alice-stack-", COMMIT_ID, "-pr");
+        assert_eq!(
+            parse_branch_name("alice-stack-", "-pr", "-base", &name),
+            Some((COMMIT_ID, BranchKind::Head))
+        );
+    }
+
+    #[test]
+    fn parse_branch_name_can_parse_base_branch_name() {
+        let name = base_branch_name("alice-stack-", COMMIT_ID, "-base");
+        assert_eq!(
+            parse_branch_name("alice-stack-", "-pr", "-base", &name),
+            Some((COMMIT_ID, BranchKind::Base))
+        );
+    }
+
+    #[test]
+    fn parse_branch_name_can_parse_custom_suffixes() {
+        let name = head_branch_name("stack/", COMMIT_ID, "/head");
+        assert_eq!(
+            parse_branch_name("stack/", "/head", "/base", &name),
+            Some((COMMIT_ID, BranchKind::Head))
+        );
+    }
+
+    #[test]
+    fn parse_branch_name_cannot_parse_other_prefix() {
+        let name = head_branch_name("bob-stack-", COMMIT_ID, "-pr");
+        assert_eq!(parse_branch_name("alice-stack-", "-pr", "-base", &name), None);
+    }
+
+    #[test]
+    fn parse_branch_name_cannot_parse_other_suffix() {
+        let name = format!("alice-stack-{}-wip", COMMIT_ID);
+        assert_eq!(parse_branch_name("alice-stack-", "-pr", "-base", &name), None);
+    }
+
+    #[test]
+    fn parse_branch_name_cannot_parse_missing_commit_id() {
+        assert_eq!(parse_branch_name("alice-stack-", "-pr", "-base", "alice-stack--pr"), None);
+    }
+
+    #[test]
+    fn sanitize_branch_component_can_keep_valid_name() {
+        assert_eq!(
+            sanitize_branch_component("alice.smith_2"),
+            Some("alice.smith_2".to_string())
+        );
+    }
+
+    #[test]
+    fn sanitize_branch_component_can_replace_invalid_characters() {
+        assert_eq!(
+            sanitize_branch_component("Alice  Smith~^:?"),
+            Some("Alice-Smith".to_string())
+        );
+    }
+
+    #[test]
+    fn sanitize_branch_component_can_collapse_dots() {
+        assert_eq!(
+            sanitize_branch_component(".alice..smith."),
+            Some("alice.smith".to_string())
+        );
+    }
+
+    #[test]
+    fn slugify_can_slugify_title() {
+        assert_eq!(
+            slugify("Fix the frobnicator's output"),
+            Some("fix-the-frobnicators-output".to_string())
+        );
+    }
+
+    #[test]
+    fn slugify_can_collapse_separators() {
+        assert_eq!(
+            slugify("  Add --all_flag - to up  "),
+            Some("add-all-flag-to-up".to_string())
+        );
+    }
+
+    #[test]
+    fn slugify_can_drop_non_ascii_characters() {
+        assert_eq!(slugify("Übersetzung für café"), Some("bersetzung-fr-caf".to_string()));
+    }
+
+    #[test]
+    fn slugify_can_truncate_at_dash() {
+        let title = "Refactor the upload of commits so that every pull request is updated at once";
+        let result = slugify(title).unwrap();
+        assert_eq!(result, "refactor-the-upload-of-commits-so-that-every-pull");
+        assert!(result.len() <= MAX_SLUG_LENGTH);
+    }
+
+    #[test]
+    fn slugify_can_truncate_long_word() {
+        let result = slugify(&"a".repeat(60)).unwrap();
+        assert_eq!(result.len(), MAX_SLUG_LENGTH);
+    }
+
+    #[test]
+    fn slugify_cannot_slugify_only_punctuation() {
+        assert_eq!(slugify("?!"), None);
+    }
+
+    #[test]
+    fn sanitize_branch_component_cannot_sanitize_only_invalid_characters() {
+        assert_eq!(sanitize_branch_component("@{}"), None);
+    }
+}
@@ -1,23 +1,127 @@
 use errors::*;
+use git2;
 use regex;
 use std;
-use std::io::Read;
+use std::fmt;
+use std::io::{Read, Write};
 use tempfile;
 
+/// Why a changeset description could not be parsed, fields are named by their label without the
+/// trailing colon.
+#[derive(Debug, PartialEq)]
+pub enum ChangesetParseError {
+    /// Nothing but comments was left in the description, which is how the editor is aborted.
+    Empty,
+    MissingTitle,
+    DuplicateField(&'static str),
+    InvalidField(&'static str, String),
+    InvalidPullRequest(String),
+    InvalidBranch(String),
+}
+
+impl fmt::Display for ChangesetParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ChangesetParseError::Empty => write!(f, "Aborting due to empty changeset."),
+            ChangesetParseError::MissingTitle => {
+                write!(f, "Could not parse title from changeset description.")
+            }
+            ChangesetParseError::DuplicateField(field) => write!(
+                f,
+                "Multiple '{}' fields found in changeset description.",
+                field
+            ),
+            ChangesetParseError::InvalidField(field, ref value) => {
+                write!(f, "Could not parse '{}' field: '{}'.", field, value)
+            }
+            ChangesetParseError::InvalidPullRequest(ref value) => write!(
+                f,
+                "Could not parse pull request number from field: '{}'.",
+                value
+            ),
+            ChangesetParseError::InvalidBranch(ref branch) => {
+                write!(f, "Invalid branch name '{}'.", branch)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ChangesetParseError {
+    fn description(&self) -> &str {
+        "invalid changeset description"
+    }
+}
+
+#[derive(Debug, PartialEq)]
 pub struct Changeset {
     pub title: String,
     pub message: Option<String>,
     pub branch: Option<String>,
+    pub base: Option<String>,
     pub pr: Option<String>,
+    pub draft: bool,
+    pub reviewers: Vec<String>,
+    pub assignees: Vec<String>,
+    pub labels: Vec<String>,
+    pub milestone: Option<String>,
+    /// Issues the pull request closes once merged.
+    pub closes: Vec<u64>,
 }
 
 impl Changeset {
     const BRANCH_FIELD_LABEL: &'static str = "Branch name:";
-    const PR_FIELD_LABEL: &'static str = "Pull request:";
+    const BASE_FIELD_LABEL: &'static str = "Base:";
+    const DRAFT_FIELD_LABEL: &'static str = "Draft:";
+    const REVIEWERS_FIELD_LABEL: &'static str = "Reviewers:";
+    const ASSIGNEES_FIELD_LABEL: &'static str = "Assignees:";
+    const LABELS_FIELD_LABEL: &'static str = "Labels:";
+    const MILESTONE_FIELD_LABEL: &'static str = "Milestone:";
+    const CLOSES_FIELD_LABEL: &'static str = "Closes:";
+    const FIXES_FIELD_LABEL: &'static str = "Fixes:";
+    /// An editor exiting sooner than this without changing the file probably didn't wait for it to
+    /// be closed, like GUI editors without their wait flag.
+    const EDITOR_MIN_MILLIS: u64 = 1000;
+    /// How long the file of such an editor is watched for the changeset to be saved.
+    const EDITOR_GRACE_PERIOD_SECS: u64 = 60;
+    /// The key of the field holding the pull request, unless `stack.prTrailer` sets another.
+    pub const DEFAULT_PR_TRAILER: &'static str = "Pull request";
+
+    /// Opens an editor on a template seeded with `commit_message`, the changeset is read from
+    /// what the editor leaves in the file.
+    pub fn new_from_editor(
+        commit_message: &str,
+        github_host: &str,
+        github_owner: &str,
+        github_repo: &str,
+        pr_trailer: &str,
+    ) -> Result<Changeset> {
+        let edited = Self::edit(&Self::editor_template(commit_message, pr_trailer))?;
+        Ok(Self::new_from_string(
+            &edited,
+            github_host,
+            github_owner,
+            github_repo,
+            pr_trailer,
+        )?)
+    }
 
-    pub fn new_from_editor(github_owner: &str, github_repo: &str) -> Result<Changeset> {
-        let mut tmpfile =
-            tempfile::NamedTempFile::new().chain_err(|| "Failed to create new temporary file.")?;
+    /// Opens `contents` in the user's editor and returns what the editor leaves in the file.
+    pub fn edit(contents: &str) -> Result<String> {
+        // The description is Markdown, which the suffix tells editors to highlight. Headings don't
+        // clash with comments, as only lines starting with a single '#' are comments.
+        let mut tmpfile = tempfile::NamedTempFileOptions::new()
+            .suffix(".md")
+            .create()
+            .chain_err(|| "Failed to create new temporary file.")?;
+        tmpfile
+            .write_all(contents.as_bytes())
+            .and_then(|_| tmpfile.flush())
+            .chain_err(|| {
+                format!(
+                    "Could not write template to temporary file '{}'.",
+                    tmpfile.path().to_string_lossy()
+                )
+            })?;
         let editor = std::env::var("VISUAL")
             .or_else(|_| {
                 std::env::var("EDITOR").or_else(
@@ -25,8 +129,10 @@ impl Changeset {
                 )
             })
             .unwrap();
-        let rc = std::process::Command::new(&editor)
-            .args(&[tmpfile.path()])
+        let editor_args = Self::editor_args(&editor, tmpfile.path())?;
+        let started = std::time::Instant::now();
+        let rc = std::process::Command::new(&editor_args[0])
+            .args(&editor_args[1..])
             .status()
             .chain_err(|| {
                 format!(
@@ -36,15 +142,55 @@ impl Changeset {
                 )
             })?;
         if rc.success() {
-            let mut buf = String::new();
-            tmpfile.read_to_string(&mut buf).chain_err(|| {
-                format!(
-                    "Could not read contents of temporary file '{}' opened with editor '{}'.",
-                    tmpfile.path().to_string_lossy(),
-                    editor
-                )
-            })?;
-            Self::new_from_string(&buf, github_owner, github_repo)
+            // Editors may replace the file rather than write to it, so it's opened again.
+            let read = || {
+                let mut buf = String::new();
+                std::fs::File::open(tmpfile.path())
+                    .and_then(|mut file| file.read_to_string(&mut buf))
+                    .map(|_| buf)
+                    .chain_err(|| {
+                        format!(
+                            "Could not read contents of temporary file '{}' opened with editor \
+                             '{}'.",
+                            tmpfile.path().to_string_lossy(),
+                            editor
+                        )
+                    })
+            };
+            let mut buf = read()?;
+            let elapsed = started.elapsed();
+            let instant = elapsed.as_secs() == 0
+                && u64::from(elapsed.subsec_nanos()) < Self::EDITOR_MIN_MILLIS * 1_000_000;
+            if instant && buf == contents {
+                // Only known GUI editors are waited for, as scripts set the editor to commands
+                // like `true` to keep the changeset as it is.
+                match Self::editor_wait_flag(&editor_args[0]) {
+                    Some(flag) => {
+                        warn!(
+                            "Editor '{}' exited right away, set VISUAL to '{} {}' to make it wait \
+                             for the file to be closed. Waiting {}s for '{}' to be saved.",
+                            editor,
+                            editor,
+                            flag,
+                            Self::EDITOR_GRACE_PERIOD_SECS,
+                            tmpfile.path().to_string_lossy()
+                        );
+                        let grace_period =
+                            std::time::Duration::from_secs(Self::EDITOR_GRACE_PERIOD_SECS);
+                        let waiting = std::time::Instant::now();
+                        while buf == contents && waiting.elapsed() < grace_period {
+                            std::thread::sleep(std::time::Duration::from_millis(500));
+                            buf = read()?;
+                        }
+                    }
+                    None => warn!(
+                        "Editor '{}' exited right away without changing the file, if it doesn't \
+                         wait for the file to be closed set VISUAL to a command that does.",
+                        editor
+                    ),
+                }
+            }
+            Ok(buf)
         } else {
             match rc.code() {
                 Some(code) => bail!(
@@ -62,114 +208,678 @@ impl Changeset {
         }
     }
 
+    /// The flag making a GUI editor that returns right away wait for the file to be closed.
+    fn editor_wait_flag<P: AsRef<std::path::Path>>(program: P) -> Option<&'static str> {
+        let name = program.as_ref().file_stem().and_then(|name| name.to_str());
+        match name.unwrap_or("") {
+            "code" | "code-insiders" | "codium" | "atom" | "zed" => Some("--wait"),
+            "subl" | "mate" => Some("-w"),
+            _ => None,
+        }
+    }
+
+    /// Splits the editor command into the program and its arguments the way a shell would, so
+    /// that editors like `code --wait` work. Quotes and backslashes escape whitespace.
+    fn split_editor_command(editor: &str) -> Result<Vec<String>> {
+        let mut args = Vec::new();
+        let mut arg = None::<String>;
+        let mut quote = None;
+        let mut chars = editor.chars();
+        while let Some(c) = chars.next() {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some('"'), '\\') | (None, '\\') => match chars.next() {
+                    Some(escaped) => arg.get_or_insert_with(String::new).push(escaped),
+                    None => bail!("Editor command '{}' ends with an escape.", editor),
+                },
+                (Some(_), c) => arg.get_or_insert_with(String::new).push(c),
+                (None, '"') | (None, '\'') => {
+                    quote = Some(c);
+                    arg.get_or_insert_with(String::new);
+                }
+                (None, c) if c.is_whitespace() => args.extend(arg.take()),
+                (None, c) => arg.get_or_insert_with(String::new).push(c),
+            }
+        }
+        if quote.is_some() {
+            bail!("Editor command '{}' has an unterminated quote.", editor);
+        }
+        args.extend(arg);
+        if args.is_empty() {
+            bail!("Editor command is empty.");
+        }
+        Ok(args)
+    }
+
+    /// The editor command line opening `path`, which is passed as the last argument.
+    fn editor_args(editor: &str, path: &std::path::Path) -> Result<Vec<std::ffi::OsString>> {
+        let mut args = Self::split_editor_command(editor)?
+            .into_iter()
+            .map(std::ffi::OsString::from)
+            .collect::<Vec<_>>();
+        args.push(path.as_os_str().to_os_string());
+        Ok(args)
+    }
+
+    fn editor_template(commit_message: &str, pr_trailer: &str) -> String {
+        format!(
+            "{}\n\n\
+             # Lines starting with a single '#' are ignored, uncomment fields to set them.\n\
+             # {}\n# {}:\n",
+            commit_message.trim(),
+            Self::BRANCH_FIELD_LABEL,
+            pr_trailer,
+        )
+    }
+
+    /// Serializes the changeset in the format `new_from_string` parses.
+    pub fn to_template_string(&self, pr_trailer: &str) -> String {
+        // Escapes lines that would otherwise be read back as comments.
+        let escape = |line: &str| {
+            if line.starts_with('#') && !line.starts_with("##") {
+                format!("\\{}", line)
+            } else {
+                line.to_string()
+            }
+        };
+        let mut lines = vec![escape(&self.title)];
+        if let Some(ref message) = self.message {
+            lines.push(String::new());
+            lines.extend(message.lines().map(&escape));
+        }
+        lines.push(String::new());
+        if let Some(ref branch) = self.branch {
+            lines.push(format!("{} {}", Self::BRANCH_FIELD_LABEL, branch));
+        }
+        if let Some(ref base) = self.base {
+            lines.push(format!("{} {}", Self::BASE_FIELD_LABEL, base));
+        }
+        if let Some(ref pr) = self.pr {
+            lines.push(Self::pull_request_field(pr_trailer, pr));
+        }
+        if self.draft {
+            lines.push(format!("{} true", Self::DRAFT_FIELD_LABEL));
+        }
+        if !self.reviewers.is_empty() {
+            lines.push(format!(
+                "{} {}",
+                Self::REVIEWERS_FIELD_LABEL,
+                self.reviewers.join(", ")
+            ));
+        }
+        if !self.assignees.is_empty() {
+            lines.push(format!(
+                "{} {}",
+                Self::ASSIGNEES_FIELD_LABEL,
+                self.assignees.join(", ")
+            ));
+        }
+        if !self.labels.is_empty() {
+            lines.push(format!("{} {}", Self::LABELS_FIELD_LABEL, self.labels.join(", ")));
+        }
+        if let Some(ref milestone) = self.milestone {
+            lines.push(format!("{} {}", Self::MILESTONE_FIELD_LABEL, milestone));
+        }
+        if !self.closes.is_empty() {
+            let issues = self.closes
+                .iter()
+                .map(|issue| format!("#{}", issue))
+                .collect::<Vec<_>>();
+            lines.push(format!("{} {}", Self::CLOSES_FIELD_LABEL, issues.join(", ")));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    /// Splits the messages of `git commit -m` style flags into the title, which is the first one,
+    /// and the description the others make as its paragraphs.
+    pub fn split_messages<'a, I: IntoIterator<Item = &'a str>>(
+        messages: I,
+    ) -> (String, Option<String>) {
+        let mut messages = messages
+            .into_iter()
+            .map(|message| message.trim())
+            .filter(|message| !message.is_empty());
+        let title = messages.next().unwrap_or("").to_string();
+        let paragraphs = messages.collect::<Vec<_>>();
+        if paragraphs.is_empty() {
+            (title, None)
+        } else {
+            (title, Some(paragraphs.join("\n\n")))
+        }
+    }
+
+    /// Joins the lines an editor hard-wrapped within each paragraph of the message, since the forge
+    /// renders it as Markdown anyway. Code blocks, headings, tables and hard line breaks are kept,
+    /// and list items start their own lines.
+    pub fn rewrap_message(&mut self) {
+        self.message = self.message.as_ref().map(|message| Self::rewrap(message));
+    }
+
+    fn rewrap(message: &str) -> String {
+        let mut lines: Vec<String> = Vec::new();
+        let mut fence: Option<&str> = None;
+        // Whether the next line may continue the last one.
+        let mut joinable = false;
+        for line in message.lines() {
+            let trimmed = line.trim_left();
+            if let Some(marker) = fence {
+                if trimmed.starts_with(marker) {
+                    fence = None;
+                }
+                lines.push(line.to_string());
+                joinable = false;
+                continue;
+            }
+            if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+                fence = Some(&trimmed[..3]);
+                lines.push(line.to_string());
+                joinable = false;
+                continue;
+            }
+            let hard_break = line.ends_with("  ") || line.ends_with('\\');
+            match lines.last_mut() {
+                Some(last) if joinable && !trimmed.is_empty() && !Self::starts_block(trimmed) => {
+                    last.push(' ');
+                    last.push_str(trimmed);
+                    joinable = !hard_break;
+                    continue;
+                }
+                _ => {}
+            }
+            lines.push(line.to_string());
+            // An indented line that doesn't continue a paragraph is code.
+            let code = line.starts_with("    ") || line.starts_with('\t');
+            joinable = !trimmed.is_empty()
+                && !hard_break
+                && !code
+                && !trimmed.starts_with('#')
+                && !trimmed.starts_with('|')
+                && !trimmed.chars().all(|c| c == '-' || c == '=' || c == '*' || c == ' ');
+        }
+        lines.join("\n")
+    }
+
+    /// Whether `line` begins a Markdown block of its own rather than continuing a paragraph.
+    fn starts_block(line: &str) -> bool {
+        let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+        let ordered =
+            digits > 0 && (line[digits..].starts_with(". ") || line[digits..].starts_with(") "));
+        ordered
+            || ["- ", "* ", "+ ", "#", ">", "|"]
+                .iter()
+                .any(|prefix| line.starts_with(prefix))
+            || line.chars().all(|c| c == '-' || c == '=' || c == '*' || c == ' ')
+    }
+
+    /// The description of the pull request, the message followed by a `Closes #N` line for each
+    /// closed issue so the forge closes them when it's merged.
+    pub fn pull_request_body(&self) -> Option<String> {
+        let closes = self.closes
+            .iter()
+            .map(|issue| format!("Closes #{}", issue))
+            .collect::<Vec<_>>();
+        match (&self.message, closes.is_empty()) {
+            (&None, true) => None,
+            (&Some(ref message), true) => Some(message.clone()),
+            (&None, false) => Some(closes.join("\n")),
+            (&Some(ref message), false) => Some(format!("{}\n\n{}", message, closes.join("\n"))),
+        }
+    }
+
+    /// Wraps the title in `template`, where `{title}` is replaced by the title and `{branch}` by
+    /// `branch`. A title that already has the form of the template is left as it is.
+    pub fn apply_title_template(&mut self, template: &str, branch: Option<&str>) -> Result<()> {
+        if !template.contains("{title}") {
+            bail!("Title template '{}' has no {{title}} placeholder.", template);
+        }
+        let mut title = String::new();
+        let mut pattern = String::from("^");
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => bail!("Unterminated placeholder in title template '{}'.", template),
+            };
+            title.push_str(&rest[..start]);
+            pattern.push_str(&regex::escape(&rest[..start]));
+            match &rest[start + 1..end] {
+                "title" => title.push_str(&self.title),
+                "branch" => match branch {
+                    Some(branch) => title.push_str(branch),
+                    None => bail!(
+                        "Title template '{}' uses {{branch}}, but HEAD is not on a branch.",
+                        template
+                    ),
+                },
+                placeholder => bail!(
+                    "Unknown placeholder '{{{}}}' in title template '{}'.",
+                    placeholder,
+                    template
+                ),
+            }
+            pattern.push_str(".+");
+            rest = &rest[end + 1..];
+        }
+        title.push_str(rest);
+        pattern.push_str(&regex::escape(rest));
+        pattern.push('$');
+        let re = regex::Regex::new(&pattern).chain_err(|| "Could not construct title regex.")?;
+        if !re.is_match(&self.title) {
+            self.title = title;
+        }
+        Ok(())
+    }
+
+    /// The field with key `pr_trailer` for `url`, to be appended to a commit message.
+    pub fn pull_request_field(pr_trailer: &str, url: &str) -> String {
+        format!("{}: {}", pr_trailer, url)
+    }
+
+    /// Reads the changeset from the commit message, so fields can be given as message trailers.
+    pub fn new_from_commit(
+        commit: &git2::Commit,
+        github_host: &str,
+        github_owner: &str,
+        github_repo: &str,
+        pr_trailer: &str,
+    ) -> Result<Changeset> {
+        let message = commit
+            .message()
+            .ok_or_else(|| format!("Commit '{}' has a non utf-8 message.", commit.id()))?;
+        Ok(Self::new_from_string(
+            message,
+            github_host,
+            github_owner,
+            github_repo,
+            pr_trailer,
+        )?)
+    }
+
+    /// Like `new_from_string`, but with `title` taking precedence over the one in `string`, which
+    /// may then have none, as in a commit made with `git commit --allow-empty-message`.
+    pub fn new_from_string_with_title(
+        string: &str,
+        title: &str,
+        github_host: &str,
+        github_owner: &str,
+        github_repo: &str,
+        pr_trailer: &str,
+    ) -> std::result::Result<Changeset, ChangesetParseError> {
+        match Self::new_from_string(string, github_host, github_owner, github_repo, pr_trailer) {
+            Ok(mut changeset) => {
+                changeset.title = title.to_string();
+                Ok(changeset)
+            }
+            Err(ChangesetParseError::Empty) | Err(ChangesetParseError::MissingTitle) => {
+                let string = format!("{}\n\n{}", title, string);
+                Self::new_from_string(&string, github_host, github_owner, github_repo, pr_trailer)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Reads a changeset from `string`, where the pull request is given by the field with key
+    /// `pr_trailer`.
     pub fn new_from_string(
         string: &str,
+        github_host: &str,
         github_owner: &str,
         github_repo: &str,
-    ) -> Result<Changeset> {
+        pr_trailer: &str,
+    ) -> std::result::Result<Changeset, ChangesetParseError> {
         let lines = string.lines();
-        let mut title = None;
+        if lines
+            .clone()
+            .all(|line| line.trim().is_empty() || Self::is_comment(line))
+        {
+            return Err(ChangesetParseError::Empty);
+        }
+        let mut title = Vec::<&str>::new();
+        let mut title_done = false;
         let mut message = Vec::<&str>::new();
         let mut branch = None;
+        let mut base = None;
         let mut pr = None;
+        let mut draft = None;
+        let mut reviewers = None;
+        let mut assignees = None;
+        let mut labels = None;
+        let mut milestone = None;
+        let mut closes = None;
+        let pr_field_label = format!("{}:", pr_trailer);
 
+        // Lines starting with a single '#' are comments. Lines starting with '##' or more are kept
+        // so Markdown headings can be used in the description, and a line that has to start with
+        // a single '#', like a level one heading, can be kept by escaping it as '\#'.
         for line in lines {
             match line {
-                x if x.is_empty() => continue,
-                x if x.starts_with('#') => continue,
+                // Like in a commit message the title is the first paragraph, so the first blank
+                // line after it starts the message. Blank lines within the message separate its
+                // paragraphs.
+                x if x.trim().is_empty() => {
+                    if title_done && !message.is_empty() && message.last() != Some(&"") {
+                        message.push("");
+                    }
+                    title_done = !title.is_empty();
+                    continue;
+                }
+                x if Self::is_comment(x) => continue,
                 x if x.starts_with(Self::BRANCH_FIELD_LABEL) => match branch {
-                    Some(_) => bail!(
-                        "Multiple 'Branch name' fields found in changeset description:\n{}",
-                        string,
-                    ),
+                    Some(_) => return Err(ChangesetParseError::DuplicateField("Branch name")),
                     None => branch = Some(x[Self::BRANCH_FIELD_LABEL.len()..].trim().to_string()),
                 },
-                x if x.starts_with(Self::PR_FIELD_LABEL) => match pr {
-                    Some(_) => bail!(
-                        "Multiple 'Pull request' fields found in changeset description:\n{}",
-                        string,
-                    ),
+                x if x.starts_with(Self::BASE_FIELD_LABEL) => match base {
+                    Some(_) => return Err(ChangesetParseError::DuplicateField("Base")),
+                    None => base = Some(x[Self::BASE_FIELD_LABEL.len()..].trim().to_string()),
+                },
+                x if x.starts_with(&pr_field_label) => match pr {
+                    Some(_) => return Err(ChangesetParseError::DuplicateField("Pull request")),
                     None => match Self::parse_pull_request(
-                        &x[Self::PR_FIELD_LABEL.len()..],
+                        &x[pr_field_label.len()..],
+                        github_host,
                         github_owner,
                         github_repo,
                     ) {
                         Ok(pr_url) => pr = Some(pr_url),
-                        Err(_) => bail!(
-                            "Could not parse pull request number from 'Pull request' field: '{}'.",
-                            x
-                        ),
+                        Err(_) => {
+                            return Err(ChangesetParseError::InvalidPullRequest(x.to_string()))
+                        }
+                    },
+                },
+                x if x.starts_with(Self::DRAFT_FIELD_LABEL) => match draft {
+                    Some(_) => return Err(ChangesetParseError::DuplicateField("Draft")),
+                    None => match Self::parse_bool(&x[Self::DRAFT_FIELD_LABEL.len()..]) {
+                        Some(value) => draft = Some(value),
+                        None => {
+                            return Err(ChangesetParseError::InvalidField("Draft", x.to_string()))
+                        }
+                    },
+                },
+                x if x.starts_with(Self::REVIEWERS_FIELD_LABEL) => match reviewers {
+                    Some(_) => return Err(ChangesetParseError::DuplicateField("Reviewers")),
+                    None => match Self::parse_users(&x[Self::REVIEWERS_FIELD_LABEL.len()..]) {
+                        Some(list) => reviewers = Some(list),
+                        None => {
+                            return Err(ChangesetParseError::InvalidField(
+                                "Reviewers",
+                                x.to_string(),
+                            ))
+                        }
                     },
                 },
-                x => match title {
-                    Some(_) => message.push(x),
-                    None => title = Some(x),
+                x if x.starts_with(Self::ASSIGNEES_FIELD_LABEL) => match assignees {
+                    Some(_) => return Err(ChangesetParseError::DuplicateField("Assignees")),
+                    None => match Self::parse_users(&x[Self::ASSIGNEES_FIELD_LABEL.len()..]) {
+                        Some(list) => assignees = Some(list),
+                        None => {
+                            return Err(ChangesetParseError::InvalidField(
+                                "Assignees",
+                                x.to_string(),
+                            ))
+                        }
+                    },
+                },
+                x if x.starts_with(Self::LABELS_FIELD_LABEL) => match labels {
+                    Some(_) => return Err(ChangesetParseError::DuplicateField("Labels")),
+                    None => match Self::parse_list(&x[Self::LABELS_FIELD_LABEL.len()..]) {
+                        Some(list) => labels = Some(list),
+                        None => {
+                            return Err(ChangesetParseError::InvalidField("Labels", x.to_string()))
+                        }
+                    },
+                },
+                x if x.starts_with(Self::MILESTONE_FIELD_LABEL) => match milestone {
+                    Some(_) => return Err(ChangesetParseError::DuplicateField("Milestone")),
+                    None => match x[Self::MILESTONE_FIELD_LABEL.len()..].trim() {
+                        "" => {
+                            return Err(ChangesetParseError::InvalidField(
+                                "Milestone",
+                                x.to_string(),
+                            ))
+                        }
+                        value => milestone = Some(value.to_string()),
+                    },
                 },
+                // 'Fixes:' is the other keyword GitHub closes issues for, both fill the same field.
+                x if x.starts_with(Self::CLOSES_FIELD_LABEL)
+                    || x.starts_with(Self::FIXES_FIELD_LABEL) =>
+                {
+                    let label_len = if x.starts_with(Self::CLOSES_FIELD_LABEL) {
+                        Self::CLOSES_FIELD_LABEL.len()
+                    } else {
+                        Self::FIXES_FIELD_LABEL.len()
+                    };
+                    match closes {
+                        Some(_) => return Err(ChangesetParseError::DuplicateField("Closes")),
+                        None => match Self::parse_issues(
+                            &x[label_len..],
+                            github_host,
+                            github_owner,
+                            github_repo,
+                        ) {
+                            Some(issues) => closes = Some(issues),
+                            None => {
+                                return Err(ChangesetParseError::InvalidField(
+                                    "Closes",
+                                    x.to_string(),
+                                ))
+                            }
+                        },
+                    }
+                }
+                x => {
+                    let x = if x.starts_with("\\#") { &x[1..] } else { x };
+                    if title_done {
+                        message.push(x);
+                    } else {
+                        title.push(x.trim());
+                    }
+                }
             }
         }
 
-        let title = title
-            .ok_or_else(|| {
-                format!(
-                    "Could not parse title from changeset description:\n{}",
-                    string
-                )
-            })?
-            .to_string();
+        if title.is_empty() {
+            return Err(ChangesetParseError::MissingTitle);
+        }
+        let title = title.join(" ");
+        // The fields usually follow the message after a blank line.
+        while message.last() == Some(&"") {
+            message.pop();
+        }
         let message = if message.is_empty() {
             None
         } else {
             Some(message.join("\n"))
         };
 
-        Ok(Changeset {
+        let changeset = Changeset {
             title,
             message,
             branch,
+            base,
             pr,
+            draft: draft.unwrap_or(false),
+            reviewers: reviewers.unwrap_or_default(),
+            assignees: assignees.unwrap_or_default(),
+            labels: labels.unwrap_or_default(),
+            milestone,
+            closes: closes.unwrap_or_default(),
+        };
+        changeset.validate()?;
+        Ok(changeset)
+    }
+
+    fn is_comment(line: &str) -> bool {
+        line.starts_with('#') && !line.starts_with("##")
+    }
+
+    /// Checks the fields that can also be set from elsewhere than `new_from_string`.
+    pub fn validate(&self) -> std::result::Result<(), ChangesetParseError> {
+        for branch in self.branch.iter().chain(self.base.iter()) {
+            // Rejects what git refuses as a branch name, like spaces, '..' or '@{'.
+            if branch.contains('\0')
+                || !git2::Reference::is_valid_name(&format!("refs/heads/{}", branch))
+            {
+                return Err(ChangesetParseError::InvalidBranch(branch.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_users(string: &str) -> Option<Vec<String>> {
+        Self::parse_list(string).map(|users| {
+            Self::dedup_ignoring_case(
+                users
+                    .iter()
+                    .map(|user| user.trim_left_matches('@').to_string())
+                    .collect(),
+            )
         })
     }
 
-    fn parse_pull_request(string: &str, github_owner: &str, github_repo: &str) -> Result<String> {
-        let pattern = format!(
-            r"^\s*(https://github.com/{}/{}/pull/|http://github.com/{0}/{1}/pull/|#)?(?P<pr_number>[0-9]+)\s*$",
+    fn parse_list(string: &str) -> Option<Vec<String>> {
+        let list = Self::dedup_ignoring_case(
+            string
+                .split(',')
+                .map(|item| item.trim())
+                .filter(|item| !item.is_empty())
+                .map(|item| item.to_string())
+                .collect(),
+        );
+        if list.is_empty() {
+            None
+        } else {
+            Some(list)
+        }
+    }
+
+    /// Keeps the first of the items only differing in case, since user names and labels are
+    /// case-insensitive and requesting them twice makes the API calls fail.
+    fn dedup_ignoring_case(items: Vec<String>) -> Vec<String> {
+        let mut deduped = Vec::<String>::new();
+        for item in items {
+            if !deduped
+                .iter()
+                .any(|known| known.to_lowercase() == item.to_lowercase())
+            {
+                deduped.push(item);
+            }
+        }
+        deduped
+    }
+
+    fn parse_bool(string: &str) -> Option<bool> {
+        match string.trim().to_lowercase().as_str() {
+            "true" | "yes" => Some(true),
+            "false" | "no" => Some(false),
+            _ => None,
+        }
+    }
+
+    pub fn pr_number(&self) -> Option<u64> {
+        self.pr
+            .as_ref()
+            .and_then(|pr| pr.rsplit('/').next())
+            .and_then(|pr_number| pr_number.parse::<u64>().ok())
+    }
+
+    /// Reads a comma separated list of issues, each given like a pull request number.
+    fn parse_issues(
+        string: &str,
+        github_host: &str,
+        github_owner: &str,
+        github_repo: &str,
+    ) -> Option<Vec<u64>> {
+        Self::parse_list(string).and_then(|issues| {
+            issues
+                .iter()
+                .map(|issue| {
+                    Self::parse_number(issue, "issues", github_host, github_owner, github_repo)
+                        .ok()
+                })
+                .collect()
+        })
+    }
+
+    /// Reads a pull request given like `parse_number` and returns its canonical url on the host,
+    /// where GitLab calls it a merge request.
+    fn parse_pull_request(
+        string: &str,
+        github_host: &str,
+        github_owner: &str,
+        github_repo: &str,
+    ) -> Result<String> {
+        let pr_number = Self::parse_number(
+            string,
+            "pull|merge_requests",
+            github_host,
             github_owner,
             github_repo,
-        );
-        let re =
-            regex::Regex::new(&pattern).chain_err(|| "Could not construct pull request regex.")?;
-        let captures = re.captures(string).ok_or_else(|| {
-            format!(
-                "Could not extract pull request number in 'Pull request' field: '{}'.",
-                string
-            )
-        })?;
-        let pr_number = captures
-            .name("pr_number")
-            .ok_or_else(|| {
-                format!(
-                    "Could not find pull request number in 'Pull request' field: '{}'.",
-                    string
-                )
-            })?
-            .as_str();
-        let pr_number = pr_number.parse::<u64>().chain_err(|| {
-            format!(
-                "Could not parse pull request number from 'Pull request' field: '{}'.",
-                pr_number
-            )
-        })?;
+        )?;
+        let host = Self::canonical_host(github_host);
+        let path = if string.contains("/merge_requests/") || host.contains("gitlab") {
+            "-/merge_requests"
+        } else {
+            "pull"
+        };
         Ok(format!(
-            "https://github.com/{}/{}/pull/{}",
-            github_owner, github_repo, pr_number,
+            "https://{}/{}/{}/{}/{}",
+            host, github_owner, github_repo, path, pr_number,
         ))
     }
+
+    /// The host as it appears in urls, also when it's configured with a scheme or a trailing slash.
+    fn canonical_host(host: &str) -> String {
+        let host = host.trim();
+        let host = ["https://", "http://"]
+            .iter()
+            .find(|scheme| host.starts_with(*scheme))
+            .map_or(host, |scheme| &host[scheme.len()..]);
+        host.trim_right_matches('/').to_lowercase()
+    }
+
+    /// Reads the number out of `N`, `#N` or a url to it, which has one of the `paths` alternatives
+    /// after the repository.
+    fn parse_number(
+        string: &str,
+        paths: &str,
+        github_host: &str,
+        github_owner: &str,
+        github_repo: &str,
+    ) -> Result<u64> {
+        // Urls copied from the browser may point into the pull request, like its files tab. Host
+        // names aren't case sensitive.
+        let pattern = format!(
+            concat!(
+                r"^\s*(https?://(?i:{})/{}/{}/(-/)?({})/(?P<url_number>[0-9]+)",
+                r"([/?#]\S*)?|#?(?P<number>[0-9]+))\s*$"
+            ),
+            regex::escape(&Self::canonical_host(github_host)),
+            regex::escape(github_owner),
+            regex::escape(github_repo),
+            paths,
+        );
+        let re = regex::Regex::new(&pattern).chain_err(|| "Could not construct number regex.")?;
+        let captures = re
+            .captures(string)
+            .ok_or_else(|| format!("Could not extract number from '{}'.", string))?;
+        let number = captures
+            .name("url_number")
+            .or_else(|| captures.name("number"))
+            .ok_or_else(|| format!("Could not find number in '{}'.", string))?
+            .as_str();
+        Ok(number
+            .parse::<u64>()
+            .chain_err(|| format!("Could not parse number '{}'.", number))?)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const PR_TRAILER: &str = Changeset::DEFAULT_PR_TRAILER;
+
     const MESSAGE_FIXTURE: &str = indoc!(
         "
 
@@ -189,155 +899,1487 @@ mod tests {
 
     #[test]
     fn new_from_string_cannot_create_from_empty_string() {
-        let result = Changeset::new_from_string("", "Coneko", "stack");
-        assert!(result.is_err());
+        let result = Changeset::new_from_string("", "github.com", "Coneko", "stack", PR_TRAILER);
+        assert_eq!(result.err(), Some(ChangesetParseError::Empty));
     }
 
     #[test]
-    fn new_from_string_cannot_create_from_string_without_title() {
+    fn new_from_string_cannot_create_from_comments_only() {
         let message = indoc!(
             "
 
-            # comment
-            Branch name: hello
-            Pull request: https://github.com/Coneko/stack/pull/1
+            # Lines starting with a single '#' are ignored.
+            # Branch name:
             "
         );
-        let result = Changeset::new_from_string(message, "Coneko", "stack");
-        assert!(result.is_err());
-    }
-
-    #[test]
-    fn new_from_string_can_create_from_string_with_only_title() {
-        let message = indoc!(
-            "
-            This is the title.
-            "
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
         );
-        let result = Changeset::new_from_string(message, "Coneko", "stack");
-        assert!(result.is_ok());
-        let result = result.unwrap();
-        assert_eq!(result.title, "This is the title.");
+        assert_eq!(result.err(), Some(ChangesetParseError::Empty));
     }
 
     #[test]
-    fn new_from_string_can_read_title() {
-        let result = Changeset::new_from_string(MESSAGE_FIXTURE, "Coneko", "stack");
-        assert!(result.is_ok());
-        let result = result.unwrap();
-        assert_eq!(result.title, "This is the title.")
-    }
+    fn editor_template_can_be_read_back() {
+        let template = Changeset::editor_template(
+            indoc!(
+                "
+                This is the title.
 
-    #[test]
-    fn new_from_string_can_read_message() {
-        let result = Changeset::new_from_string(MESSAGE_FIXTURE, "Coneko", "stack");
-        assert!(result.is_ok());
-        let result = result.unwrap();
-        assert!(result.message.is_some());
-        let message = result.message.unwrap();
-        assert_eq!(
-            message,
-            "This is the first line of the description.\nThis is the second line of the description.",
+                This is the description.
+                "
+            ),
+            PR_TRAILER,
+        );
+        let result = Changeset::new_from_string(
+            &template,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
         );
-    }
-
-    #[test]
-    fn new_from_string_can_read_branch() {
-        let result = Changeset::new_from_string(MESSAGE_FIXTURE, "Coneko", "stack");
         assert!(result.is_ok());
         let result = result.unwrap();
-        assert!(result.branch.is_some());
-        let branch = result.branch.unwrap();
-        assert_eq!(branch, "hello".to_string());
+        assert_eq!(result.title, "This is the title.");
+        assert_eq!(result.message, Some("This is the description.".to_string()));
+        assert_eq!(result.branch, None);
+        assert_eq!(result.pr, None);
     }
 
     #[test]
-    fn new_from_string_can_read_pr() {
-        let result = Changeset::new_from_string(MESSAGE_FIXTURE, "Coneko", "stack");
-        assert!(result.is_ok());
-        let result = result.unwrap();
-        assert!(result.pr.is_some());
-        let pr = result.pr.unwrap();
-        assert_eq!(pr, "https://github.com/Coneko/stack/pull/4");
+    fn editor_template_has_commented_out_fields() {
+        let template = Changeset::editor_template("This is the title.", PR_TRAILER);
+        assert!(template.lines().any(|line| line == "# Branch name:"));
+        assert!(template.lines().any(|line| line == "# Pull request:"));
     }
 
     #[test]
-    fn new_from_string_cannot_create_from_string_with_multiple_pr_fields() {
+    fn new_from_string_keeps_markdown_headings() {
         let message = indoc!(
             "
             This is the title.
+            # comment
 
-            Pull request: https://github.com/Coneko/stack/pull/1
-            This is the longer description of the commit.
-            Pull request: https://github.com/Coneko/stack/pull/1
+            ## Summary
+            This is the description.
+            ### Details
             "
         );
-        let result = Changeset::new_from_string(message, "Coneko", "stack");
-        assert!(result.is_err());
-        let result = result.err().unwrap();
-        assert!(result.description().contains("Multiple"));
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap().message,
+            Some("## Summary\nThis is the description.\n### Details".to_string())
+        );
     }
 
     #[test]
-    fn new_from_string_cannot_create_from_string_with_invalid_pr_field() {
+    fn new_from_string_keeps_escaped_comment_lines() {
         let message = indoc!(
             "
             This is the title.
 
-            Pull request: hello
+            \\# Heading
+            # comment
             "
         );
-        let result = Changeset::new_from_string(message, "Coneko", "stack");
-        assert!(result.is_err());
-        let result = result.err().unwrap();
-        assert!(result.description().contains("Could not parse"));
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().message, Some("# Heading".to_string()));
     }
 
     #[test]
-    fn parse_pull_request_cannot_parse_pr_from_empty_string() {
-        let result = Changeset::parse_pull_request("", "Coneko", "stack");
-        assert!(result.is_err());
+    fn split_editor_command_can_split_single_word() {
+        let result = Changeset::split_editor_command("vi");
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), vec!["vi"]);
     }
 
     #[test]
-    fn parse_pull_request_cannot_parse_invalid_pr_field() {
-        let result = Changeset::parse_pull_request("not a valid PR reference", "Coneko", "stack");
-        assert!(result.is_err());
+    fn split_editor_command_can_split_arguments() {
+        let result = Changeset::split_editor_command("code --wait");
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), vec!["code", "--wait"]);
+        let result = Changeset::split_editor_command("  emacsclient   -c ");
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), vec!["emacsclient", "-c"]);
     }
 
     #[test]
-    fn parse_pull_request_can_parse_number() {
-        let result = Changeset::parse_pull_request("1", "Coneko", "stack");
+    fn split_editor_command_can_split_quoted_arguments() {
+        let result =
+            Changeset::split_editor_command("'/Applications/Sublime Text/subl' -n \"\" -w");
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "https://github.com/Coneko/stack/pull/1");
+        assert_eq!(
+            result.unwrap(),
+            vec!["/Applications/Sublime Text/subl", "-n", "", "-w"]
+        );
+    }
+
+    #[test]
+    fn split_messages_can_split_single_message() {
+        assert_eq!(
+            Changeset::split_messages(vec!["This is the title."]),
+            ("This is the title.".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn split_messages_can_join_paragraphs() {
+        assert_eq!(
+            Changeset::split_messages(vec!["This is the title.", "First.", "Second.\nMore."]),
+            (
+                "This is the title.".to_string(),
+                Some("First.\n\nSecond.\nMore.".to_string())
+            )
+        );
+    }
+
+    #[test]
+    fn split_messages_can_skip_empty_messages() {
+        assert_eq!(
+            Changeset::split_messages(vec!["", " This is the title. ", "  ", "Body."]),
+            ("This is the title.".to_string(), Some("Body.".to_string()))
+        );
+    }
+
+    #[test]
+    fn rewrap_message_can_join_paragraph_lines() {
+        let mut changeset = changeset_with_title("This is the title.");
+        changeset.message = Some(
+            "This is a paragraph\nwrapped by the editor.\n\nAnother one,\nkept apart.  \nAfter a \
+             break."
+                .to_string(),
+        );
+        changeset.rewrap_message();
+        assert_eq!(
+            changeset.message,
+            Some(
+                "This is a paragraph wrapped by the editor.\n\nAnother one, kept apart.  \nAfter a \
+                 break."
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn rewrap_message_can_keep_parsed_paragraphs_apart() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            This is a paragraph
+            wrapped by the editor.
+
+            Another one.
+
+            Reviewers: alice
+            "
+        );
+        let mut changeset = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        ).unwrap();
+        changeset.rewrap_message();
+        assert_eq!(
+            changeset.message,
+            Some("This is a paragraph wrapped by the editor.\n\nAnother one.".to_string())
+        );
+    }
+
+    #[test]
+    fn rewrap_message_can_keep_lists_and_headings() {
+        let mut changeset = changeset_with_title("This is the title.");
+        changeset.message = Some(
+            "# Changes\n- First item\n  wrapped.\n- Second item.\n1. Ordered\nitem.".to_string(),
+        );
+        changeset.rewrap_message();
+        assert_eq!(
+            changeset.message,
+            Some("# Changes\n- First item wrapped.\n- Second item.\n1. Ordered item.".to_string())
+        );
+    }
+
+    #[test]
+    fn rewrap_message_can_preserve_code_fences() {
+        let mut changeset = changeset_with_title("This is the title.");
+        changeset.message = Some(
+            "Run\nthis:\n```sh\nstack up\n  --draft\n```\nThen\nthat:\n~~~\na\nb\n~~~".to_string(),
+        );
+        changeset.rewrap_message();
+        assert_eq!(
+            changeset.message,
+            Some(
+                "Run this:\n```sh\nstack up\n  --draft\n```\nThen that:\n~~~\na\nb\n~~~"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn editor_wait_flag_can_find_flag_of_gui_editor() {
+        assert_eq!(Changeset::editor_wait_flag("code"), Some("--wait"));
+        assert_eq!(
+            Changeset::editor_wait_flag("/Applications/Sublime Text/subl"),
+            Some("-w")
+        );
+    }
+
+    #[test]
+    fn editor_wait_flag_cannot_find_flag_of_terminal_editor() {
+        assert_eq!(Changeset::editor_wait_flag("vim"), None);
+    }
+
+    #[test]
+    fn split_editor_command_can_split_escaped_whitespace() {
+        let result = Changeset::split_editor_command("/opt/my\\ editor/bin/edit --wait");
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), vec!["/opt/my editor/bin/edit", "--wait"]);
+    }
+
+    #[test]
+    fn split_editor_command_cannot_split_empty_string() {
+        let result = Changeset::split_editor_command("  ");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn split_editor_command_cannot_split_unterminated_quote() {
+        let result = Changeset::split_editor_command("code \"--wait");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn editor_args_appends_path_last() {
+        let result = Changeset::editor_args("code --wait", std::path::Path::new("/tmp/changeset"));
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), vec!["code", "--wait", "/tmp/changeset"]);
+    }
+
+    #[test]
+    fn to_template_string_round_trips() {
+        let changeset = Changeset {
+            title: "This is the title.".to_string(),
+            message: Some("## Summary\n# Not a comment\nThis is the description.".to_string()),
+            branch: Some("hello".to_string()),
+            base: Some("develop".to_string()),
+            pr: Some("https://github.com/Coneko/stack/pull/1".to_string()),
+            draft: true,
+            reviewers: vec!["alice".to_string(), "bob".to_string()],
+            assignees: vec!["carol".to_string()],
+            labels: vec!["bug".to_string()],
+            milestone: Some("v1.0".to_string()),
+            closes: vec![12, 34],
+        };
+        let result = Changeset::new_from_string(
+            &changeset.to_template_string(PR_TRAILER),
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), changeset);
+    }
+
+    #[test]
+    fn to_template_string_round_trips_fixture() {
+        let result = Changeset::new_from_string(
+            MESSAGE_FIXTURE,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        ).unwrap();
+        let round_trip = Changeset::new_from_string(
+            &result.to_template_string(PR_TRAILER),
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(round_trip.is_ok());
+        assert_eq!(round_trip.unwrap(), result);
+    }
+
+    #[test]
+    fn new_from_string_cannot_create_from_string_without_title() {
+        let message = indoc!(
+            "
+
+            # comment
+            Branch name: hello
+            Pull request: https://github.com/Coneko/stack/pull/1
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert_eq!(result.err(), Some(ChangesetParseError::MissingTitle));
+    }
+
+    #[test]
+    fn new_from_string_can_create_from_string_with_only_title() {
+        let message = indoc!(
+            "
+            This is the title.
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        let result = result.unwrap();
+        assert_eq!(result.title, "This is the title.");
+    }
+
+    #[test]
+    fn new_from_string_can_read_title() {
+        let result = Changeset::new_from_string(
+            MESSAGE_FIXTURE,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        let result = result.unwrap();
+        assert_eq!(result.title, "This is the title.")
+    }
+
+    #[test]
+    fn new_from_string_can_read_multi_line_title() {
+        let message = indoc!(
+            "
+            This is a long title
+            # comment
+            that goes on.
+
+            This is the description.
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        let result = result.unwrap();
+        assert_eq!(result.title, "This is a long title that goes on.");
+        assert_eq!(result.message, Some("This is the description.".to_string()));
+    }
+
+    #[test]
+    fn new_from_string_can_read_title_without_message() {
+        let message = indoc!(
+            "
+            This is the title
+            on two lines.
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        let result = result.unwrap();
+        assert_eq!(result.title, "This is the title on two lines.");
+        assert_eq!(result.message, None);
+    }
+
+    #[test]
+    fn new_from_string_with_title_can_create_from_empty_string() {
+        let result = Changeset::new_from_string_with_title(
+            "  \n",
+            "This is the title.",
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        let result = result.unwrap();
+        assert_eq!(result.title, "This is the title.");
+        assert_eq!(result.message, None);
+    }
+
+    #[test]
+    fn new_from_string_with_title_can_create_from_fields_only() {
+        let result = Changeset::new_from_string_with_title(
+            "Pull request: #4\n",
+            "This is the title.",
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        let result = result.unwrap();
+        assert_eq!(result.title, "This is the title.");
+        assert_eq!(result.pr_number(), Some(4));
+    }
+
+    #[test]
+    fn new_from_string_with_title_overrides_title() {
+        let result = Changeset::new_from_string_with_title(
+            MESSAGE_FIXTURE,
+            "This is another title.",
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        let result = result.unwrap();
+        assert_eq!(result.title, "This is another title.");
+        assert_eq!(result.branch, Some("hello".to_string()));
+    }
+
+    #[test]
+    fn new_from_string_can_read_message() {
+        let result = Changeset::new_from_string(
+            MESSAGE_FIXTURE,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        let result = result.unwrap();
+        assert!(result.message.is_some());
+        let message = result.message.unwrap();
+        assert_eq!(
+            message,
+            "This is the first line of the description.\nThis is the second line of the description.",
+        );
+    }
+
+    #[test]
+    fn new_from_string_can_read_multi_paragraph_message() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            This is the first paragraph.
+
+            This is the second paragraph,
+            on two lines.
+
+            This is the third paragraph.
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        ).unwrap();
+        assert_eq!(
+            result.message,
+            Some(
+                "This is the first paragraph.\n\nThis is the second paragraph,\non two lines.\n\n\
+                 This is the third paragraph."
+                    .to_string()
+            )
+        );
+        let round_trip = Changeset::new_from_string(
+            &result.to_template_string(PR_TRAILER),
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        ).unwrap();
+        assert_eq!(round_trip, result);
+    }
+
+    #[test]
+    fn new_from_string_can_read_branch() {
+        let result = Changeset::new_from_string(
+            MESSAGE_FIXTURE,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        let result = result.unwrap();
+        assert!(result.branch.is_some());
+        let branch = result.branch.unwrap();
+        assert_eq!(branch, "hello".to_string());
+    }
+
+    #[test]
+    fn new_from_string_can_read_pr() {
+        let result = Changeset::new_from_string(
+            MESSAGE_FIXTURE,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        let result = result.unwrap();
+        assert!(result.pr.is_some());
+        let pr = result.pr.unwrap();
+        assert_eq!(pr, "https://github.com/Coneko/stack/pull/4");
+    }
+
+    #[test]
+    fn new_from_string_cannot_create_from_string_with_multiple_pr_fields() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            Pull request: https://github.com/Coneko/stack/pull/1
+            This is the longer description of the commit.
+            Pull request: https://github.com/Coneko/stack/pull/1
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert_eq!(result.err(), Some(ChangesetParseError::DuplicateField("Pull request")));
+    }
+
+    #[test]
+    fn new_from_string_cannot_create_from_string_with_invalid_pr_field() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            Pull request: hello
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert_eq!(
+            result.err(),
+            Some(ChangesetParseError::InvalidPullRequest("Pull request: hello".to_string()))
+        );
+    }
+
+    #[test]
+    fn new_from_string_is_not_draft_by_default() {
+        let result = Changeset::new_from_string(
+            MESSAGE_FIXTURE,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        assert!(!result.unwrap().draft);
+    }
+
+    #[test]
+    fn new_from_string_can_read_draft() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            Draft: true
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        assert!(result.unwrap().draft);
+    }
+
+    #[test]
+    fn new_from_string_cannot_create_from_string_with_invalid_draft_field() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            Draft: maybe
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert_eq!(
+            result.err(),
+            Some(ChangesetParseError::InvalidField("Draft", "Draft: maybe".to_string()))
+        );
+    }
+
+    #[test]
+    fn new_from_string_can_read_single_reviewer() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            Reviewers: alice
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().reviewers, vec!["alice".to_string()]);
+    }
+
+    #[test]
+    fn new_from_string_can_read_multiple_reviewers() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            Reviewers: alice, @bob,carol
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap().reviewers,
+            vec!["alice".to_string(), "bob".to_string(), "carol".to_string()]
+        );
+    }
+
+    #[test]
+    fn new_from_string_cannot_create_from_string_with_empty_reviewers_field() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            Reviewers: ,
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert_eq!(
+            result.err(),
+            Some(ChangesetParseError::InvalidField("Reviewers", "Reviewers: ,".to_string()))
+        );
+    }
+
+    #[test]
+    fn new_from_string_cannot_create_from_string_with_multiple_reviewers_fields() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            Reviewers: alice
+            Reviewers: bob
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert_eq!(result.err(), Some(ChangesetParseError::DuplicateField("Reviewers")));
+    }
+
+    #[test]
+    fn new_from_string_can_read_assignees() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            Assignees: @alice, bob
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap().assignees,
+            vec!["alice".to_string(), "bob".to_string()]
+        );
+    }
+
+    #[test]
+    fn new_from_string_cannot_create_from_string_with_empty_assignees_field() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            Assignees:
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert_eq!(
+            result.err(),
+            Some(ChangesetParseError::InvalidField("Assignees", "Assignees:".to_string()))
+        );
+    }
+
+    #[test]
+    fn new_from_string_cannot_create_from_string_with_multiple_assignees_fields() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            Assignees: alice
+            Assignees: bob
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert_eq!(result.err(), Some(ChangesetParseError::DuplicateField("Assignees")));
+    }
+
+    #[test]
+    fn new_from_string_cannot_create_from_string_with_space_in_branch_name() {
+        let result = Changeset::new_from_string(
+            "This is the title.\nBranch name: hello world",
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert_eq!(
+            result.err(),
+            Some(ChangesetParseError::InvalidBranch("hello world".to_string()))
+        );
+    }
+
+    #[test]
+    fn new_from_string_cannot_create_from_string_with_leading_slash_in_branch_name() {
+        let result = Changeset::new_from_string(
+            "This is the title.\nBranch name: /hello",
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn new_from_string_cannot_create_from_string_with_reflog_syntax_in_branch_name() {
+        let result = Changeset::new_from_string(
+            "This is the title.\nBranch name: hello@{1}",
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn new_from_string_can_read_base() {
+        let result = Changeset::new_from_string(
+            "This is the title.\nBase: develop",
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().base, Some("develop".to_string()));
+    }
+
+    #[test]
+    fn new_from_string_cannot_create_from_string_with_space_in_base() {
+        let result = Changeset::new_from_string(
+            "This is the title.\nBase: hello world",
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert_eq!(
+            result.err(),
+            Some(ChangesetParseError::InvalidBranch("hello world".to_string()))
+        );
+    }
+
+    #[test]
+    fn new_from_string_can_read_branch_name_with_slashes() {
+        let result = Changeset::new_from_string(
+            "This is the title.\nBranch name: alice/hello",
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().branch, Some("alice/hello".to_string()));
+    }
+
+    #[test]
+    fn new_from_string_can_read_labels() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            Labels:   stacked ,  needs-review  ,
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap().labels,
+            vec!["stacked".to_string(), "needs-review".to_string()]
+        );
+    }
+
+    #[test]
+    fn new_from_string_can_read_milestone() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            Milestone:  Release 2.0
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().milestone, Some("Release 2.0".to_string()));
+    }
+
+    #[test]
+    fn new_from_string_cannot_create_from_string_with_multiple_milestone_fields() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            Milestone: v1.0
+            Milestone: v2.0
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert_eq!(
+            result.err(),
+            Some(ChangesetParseError::DuplicateField("Milestone"))
+        );
+    }
+
+    #[test]
+    fn new_from_string_cannot_create_from_string_with_empty_milestone_field() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            Milestone:
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert_eq!(
+            result.err(),
+            Some(ChangesetParseError::InvalidField(
+                "Milestone",
+                "Milestone:".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn new_from_string_can_read_closes_with_mixed_references() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            Closes: 12, #34, https://github.com/Coneko/stack/issues/56
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().closes, vec![12, 34, 56]);
+    }
+
+    #[test]
+    fn new_from_string_can_read_fixes() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            Fixes: #7
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().closes, vec![7]);
     }
 
     #[test]
-    fn parse_pull_request_can_parse_pr_reference() {
-        let result = Changeset::parse_pull_request("#1", "Coneko", "stack");
+    fn new_from_string_cannot_create_from_string_with_closes_and_fixes_fields() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            Closes: #1
+            Fixes: #2
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert_eq!(
+            result.err(),
+            Some(ChangesetParseError::DuplicateField("Closes"))
+        );
+    }
+
+    #[test]
+    fn new_from_string_cannot_create_from_string_with_issue_url_for_other_repo() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            Closes: #1, https://github.com/Coneko/other/issues/2
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert_eq!(
+            result.err(),
+            Some(ChangesetParseError::InvalidField(
+                "Closes",
+                "Closes: #1, https://github.com/Coneko/other/issues/2".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn pull_request_body_can_link_closed_issues() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            This is the description.
+
+            Closes: #1, 2
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        ).unwrap();
+        assert_eq!(
+            result.pull_request_body(),
+            Some("This is the description.\n\nCloses #1\nCloses #2".to_string())
+        );
+    }
+
+    #[test]
+    fn pull_request_body_can_link_closed_issues_without_message() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            Fixes: 3
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        ).unwrap();
+        assert_eq!(result.pull_request_body(), Some("Closes #3".to_string()));
+    }
+
+    fn changeset_with_title(title: &str) -> Changeset {
+        Changeset::new_from_string(title, "github.com", "Coneko", "stack", PR_TRAILER).unwrap()
+    }
+
+    #[test]
+    fn apply_title_template_can_expand_placeholders() {
+        let mut changeset = changeset_with_title("Fix the frobnicator");
+        let result = changeset.apply_title_template("[{branch}] {title}", Some("JIRA-123"));
+        assert!(result.is_ok());
+        assert_eq!(changeset.title, "[JIRA-123] Fix the frobnicator");
+    }
+
+    #[test]
+    fn apply_title_template_cannot_wrap_title_twice() {
+        let mut changeset = changeset_with_title("[JIRA-123] Fix the frobnicator");
+        let result = changeset.apply_title_template("[{branch}] {title}", Some("JIRA-123"));
+        assert!(result.is_ok());
+        assert_eq!(changeset.title, "[JIRA-123] Fix the frobnicator");
+    }
+
+    #[test]
+    fn apply_title_template_cannot_expand_branch_without_branch() {
+        let mut changeset = changeset_with_title("Fix the frobnicator");
+        let result = changeset.apply_title_template("[{branch}] {title}", None);
+        assert!(result.is_err());
+        assert_eq!(changeset.title, "Fix the frobnicator");
+    }
+
+    #[test]
+    fn apply_title_template_cannot_expand_unknown_placeholder() {
+        let mut changeset = changeset_with_title("Fix the frobnicator");
+        let result = changeset.apply_title_template("{ticket}: {title}", Some("main"));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn apply_title_template_cannot_apply_template_without_title() {
+        let mut changeset = changeset_with_title("Fix the frobnicator");
+        let result = changeset.apply_title_template("[{branch}]", Some("JIRA-123"));
+        assert!(result.is_err());
+        assert_eq!(changeset.title, "Fix the frobnicator");
+    }
+
+    #[test]
+    fn new_from_string_can_dedup_reviewers_ignoring_case() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            Reviewers: Alice, @alice, bob, ALICE, @Bob
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap().reviewers,
+            vec!["Alice".to_string(), "bob".to_string()]
+        );
+    }
+
+    #[test]
+    fn new_from_string_can_dedup_assignees_ignoring_case() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            Assignees: carol, Carol
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().assignees, vec!["carol".to_string()]);
+    }
+
+    #[test]
+    fn new_from_string_can_dedup_labels_ignoring_case() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            Labels: Bug, stacked, bug
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap().labels,
+            vec!["Bug".to_string(), "stacked".to_string()]
+        );
+    }
+
+    #[test]
+    fn new_from_string_has_no_labels_by_default() {
+        let result = Changeset::new_from_string(
+            MESSAGE_FIXTURE,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        assert!(result.unwrap().labels.is_empty());
+    }
+
+    #[test]
+    fn new_from_string_cannot_create_from_string_with_empty_labels_field() {
+        let message = indoc!(
+            "
+            This is the title.
+
+            Labels:
+            "
+        );
+        let result = Changeset::new_from_string(
+            message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert_eq!(
+            result.err(),
+            Some(ChangesetParseError::InvalidField("Labels", "Labels:".to_string()))
+        );
+    }
+
+    #[test]
+    fn pr_number_is_none_without_pr() {
+        let result = Changeset::new_from_string(
+            "This is the title.",
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().pr_number(), None);
+    }
+
+    #[test]
+    fn pr_number_can_read_pr_number() {
+        let result = Changeset::new_from_string(
+            MESSAGE_FIXTURE,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "https://github.com/Coneko/stack/pull/1");
+        assert_eq!(result.unwrap().pr_number(), Some(4));
+    }
+
+    /// Pull request fields are read the same way on every host.
+    const HOSTS: &[&str] = &["github.com", "github.example.com"];
+
+    #[test]
+    fn parse_pull_request_cannot_parse_pr_from_empty_string() {
+        for host in HOSTS {
+            let result = Changeset::parse_pull_request("", host, "Coneko", "stack");
+            assert!(result.is_err());
+        }
+    }
+
+    #[test]
+    fn parse_pull_request_cannot_parse_invalid_pr_field() {
+        for host in HOSTS {
+            let result =
+                Changeset::parse_pull_request("not a valid PR reference", host, "Coneko", "stack");
+            assert!(result.is_err());
+        }
+    }
+
+    #[test]
+    fn parse_pull_request_can_parse_number() {
+        for host in HOSTS {
+            let result = Changeset::parse_pull_request("1", host, "Coneko", "stack");
+            assert!(result.is_ok());
+            assert_eq!(result.unwrap(), format!("https://{}/Coneko/stack/pull/1", host));
+        }
+    }
+
+    #[test]
+    fn parse_pull_request_can_parse_pr_reference() {
+        for host in HOSTS {
+            let result = Changeset::parse_pull_request("#1", host, "Coneko", "stack");
+            assert!(result.is_ok());
+            assert_eq!(result.unwrap(), format!("https://{}/Coneko/stack/pull/1", host));
+        }
     }
 
     #[test]
     fn parse_pull_request_can_parse_https_url() {
+        for host in HOSTS {
+            let url = format!("https://{}/Coneko/stack/pull/1", host);
+            let result = Changeset::parse_pull_request(&url, host, "Coneko", "stack");
+            assert!(result.is_ok());
+            assert_eq!(result.unwrap(), url);
+        }
+    }
+
+    #[test]
+    fn parse_pull_request_can_parse_merge_request_url() {
         let result = Changeset::parse_pull_request(
-            "https://github.com/Coneko/stack/pull/1",
+            "https://gitlab.com/Coneko/stack/merge_requests/1",
+            "gitlab.com",
             "Coneko",
             "stack",
         );
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "https://github.com/Coneko/stack/pull/1");
+        assert_eq!(
+            result.unwrap(),
+            "https://gitlab.com/Coneko/stack/-/merge_requests/1"
+        );
     }
 
     #[test]
-    fn parse_pull_request_can_parse_http_url() {
+    fn parse_pull_request_can_parse_number_on_gitlab() {
+        let result = Changeset::parse_pull_request("1", "gitlab.example.com", "Coneko", "stack");
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap(),
+            "https://gitlab.example.com/Coneko/stack/-/merge_requests/1"
+        );
+    }
+
+    #[test]
+    fn new_from_string_can_read_pull_request_field() {
+        let message = format!(
+            "This is the title.\n\n{}",
+            Changeset::pull_request_field(PR_TRAILER, "https://github.com/Coneko/stack/pull/1")
+        );
+        let result = Changeset::new_from_string(
+            &message,
+            "github.com",
+            "Coneko",
+            "stack",
+            PR_TRAILER,
+        );
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().pr_number(), Some(1));
+    }
+
+    #[test]
+    fn new_from_string_can_read_custom_pr_trailer() {
+        let message = format!(
+            "This is the title.\n\n{}",
+            Changeset::pull_request_field("Reviewed-on", "https://github.com/Coneko/stack/pull/2")
+        );
+        let result =
+            Changeset::new_from_string(&message, "github.com", "Coneko", "stack", "Reviewed-on");
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().pr_number(), Some(2));
+    }
+
+    #[test]
+    fn new_from_string_cannot_read_default_pr_trailer_when_custom() {
+        let message = "This is the title.\n\nPull request: 1\n";
+        let result =
+            Changeset::new_from_string(message, "github.com", "Coneko", "stack", "Reviewed-on");
+        assert!(result.is_ok());
+        let result = result.unwrap();
+        assert_eq!(result.pr, None);
+        assert_eq!(result.message, Some("Pull request: 1".to_string()));
+    }
+
+    #[test]
+    fn to_template_string_can_round_trip_custom_pr_trailer() {
+        let mut changeset = changeset_with_title("This is the title.");
+        changeset.pr = Some("https://github.com/Coneko/stack/pull/3".to_string());
+        let template = changeset.to_template_string("Reviewed-on");
+        assert!(template.contains("Reviewed-on: https://github.com/Coneko/stack/pull/3"));
+        let result =
+            Changeset::new_from_string(&template, "github.com", "Coneko", "stack", "Reviewed-on");
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), changeset);
+    }
+
+    #[test]
+    fn parse_pull_request_can_parse_url_for_host_with_scheme() {
         let result = Changeset::parse_pull_request(
-            "http://github.com/Coneko/stack/pull/1",
+            "https://GitHub.Example.com/Coneko/stack/pull/1",
+            "https://github.example.com/",
             "Coneko",
             "stack",
         );
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "https://github.com/Coneko/stack/pull/1");
+        assert_eq!(
+            result.unwrap(),
+            "https://github.example.com/Coneko/stack/pull/1"
+        );
+    }
+
+    #[test]
+    fn parse_pull_request_cannot_parse_url_for_other_host() {
+        let result = Changeset::parse_pull_request(
+            "https://github.com/Coneko/stack/pull/1",
+            "github.example.com",
+            "Coneko",
+            "stack",
+        );
+        assert!(result.is_err());
+        let result = Changeset::parse_pull_request(
+            "https://github.example.com/Coneko/stack/pull/1",
+            "github.com",
+            "Coneko",
+            "stack",
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn parse_pull_request_can_parse_url_with_trailing_path() {
+        for host in HOSTS {
+            let url = format!("https://{}/Coneko/stack/pull/12/files", host);
+            let result = Changeset::parse_pull_request(&url, host, "Coneko", "stack");
+            assert!(result.is_ok());
+            assert_eq!(result.unwrap(), format!("https://{}/Coneko/stack/pull/12", host));
+        }
+    }
+
+    #[test]
+    fn parse_pull_request_can_parse_url_with_fragment() {
+        for host in HOSTS {
+            let url = format!("https://{}/Coneko/stack/pull/12#issue-123456", host);
+            let result = Changeset::parse_pull_request(&url, host, "Coneko", "stack");
+            assert!(result.is_ok());
+            assert_eq!(result.unwrap(), format!("https://{}/Coneko/stack/pull/12", host));
+        }
+    }
+
+    #[test]
+    fn parse_pull_request_can_parse_url_with_query_string() {
+        for host in HOSTS {
+            let url = format!("https://{}/Coneko/stack/pull/12?w=1", host);
+            let result = Changeset::parse_pull_request(&url, host, "Coneko", "stack");
+            assert!(result.is_ok());
+            assert_eq!(result.unwrap(), format!("https://{}/Coneko/stack/pull/12", host));
+        }
+    }
+
+    #[test]
+    fn parse_pull_request_cannot_parse_url_with_invalid_number() {
+        for host in HOSTS {
+            let url = format!("https://{}/Coneko/stack/pull/12x", host);
+            let result = Changeset::parse_pull_request(&url, host, "Coneko", "stack");
+            assert!(result.is_err());
+        }
+    }
+
+    #[test]
+    fn parse_pull_request_can_parse_http_url() {
+        for host in HOSTS {
+            let url = format!("http://{}/Coneko/stack/pull/1", host);
+            let result = Changeset::parse_pull_request(&url, host, "Coneko", "stack");
+            assert!(result.is_ok());
+            assert_eq!(result.unwrap(), format!("https://{}/Coneko/stack/pull/1", host));
+        }
     }
 }
@@ -0,0 +1,219 @@
+use errors::*;
+use git2;
+use regex;
+use std;
+use std::io::Read;
+
+struct Rule {
+    pattern: regex::Regex,
+    owners: Vec<String>,
+}
+
+/// The owners of the paths in the repository, read from a GitHub `CODEOWNERS` file.
+pub struct CodeOwners {
+    rules: Vec<Rule>,
+}
+
+impl CodeOwners {
+    /// Where GitHub looks for the file, in the order it looks.
+    const PATHS: &'static [&'static str] = &[".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+    /// Loads the code owners of the repository, `None` if it has no `CODEOWNERS` file.
+    pub fn load(repo: &git2::Repository) -> Result<Option<CodeOwners>> {
+        let workdir = match repo.workdir() {
+            Some(workdir) => workdir,
+            None => return Ok(None),
+        };
+        for path in Self::PATHS {
+            let path = workdir.join(path);
+            let mut file = match std::fs::File::open(&path) {
+                Ok(file) => file,
+                Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => {
+                    return Err(e).chain_err(|| format!("Could not open '{}'.", path.display()))
+                }
+            };
+            let mut contents = String::new();
+            file.read_to_string(&mut contents)
+                .chain_err(|| format!("Could not read '{}'.", path.display()))?;
+            return Self::new_from_string(&contents)
+                .map(Some)
+                .chain_err(|| format!("Invalid '{}'.", path.display()));
+        }
+        Ok(None)
+    }
+
+    pub fn new_from_string(string: &str) -> Result<CodeOwners> {
+        let mut rules = Vec::new();
+        for line in string.lines() {
+            let mut words = line.split_whitespace().take_while(|word| !word.starts_with('#'));
+            let pattern = match words.next() {
+                Some(pattern) => pattern,
+                None => continue,
+            };
+            rules.push(Rule {
+                pattern: Self::pattern_regex(pattern)?,
+                owners: words.map(|owner| owner.to_string()).collect(),
+            });
+        }
+        Ok(CodeOwners { rules })
+    }
+
+    /// Translates a gitignore style pattern: one without a slash but at its end matches at any
+    /// depth, and one matching a directory matches everything in it.
+    fn pattern_regex(pattern: &str) -> Result<regex::Regex> {
+        let anchored = pattern.trim_right_matches('/').contains('/');
+        let glob = pattern.trim_left_matches('/');
+        let mut re = String::from(if anchored { "^" } else { "^(.*/)?" });
+        let mut chars = glob.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    if chars.peek() == Some(&'/') {
+                        chars.next();
+                        re.push_str("(.*/)?");
+                    } else {
+                        re.push_str(".*");
+                    }
+                }
+                '*' => re.push_str("[^/]*"),
+                '?' => re.push_str("[^/]"),
+                c => re.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        // 'docs/*' matches the files in 'docs' but not those in its subdirectories.
+        if glob.ends_with('/') {
+            re.push_str(".*");
+        } else if !glob.ends_with('*') || glob.ends_with("**") {
+            re.push_str("(/.*)?");
+        }
+        re.push('$');
+        regex::Regex::new(&re).chain_err(|| format!("Invalid pattern '{}'.", pattern))
+    }
+
+    /// The owners of `path`, given by the last rule matching it.
+    pub fn owners(&self, path: &str) -> &[String] {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.pattern.is_match(path))
+            .map(|rule| rule.owners.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// The users and teams owning any of `paths` as reviewers, `user` or `org/team` without the
+    /// leading '@'. Owners given by email can't be requested as reviewers and are left out.
+    pub fn reviewers<'a, I: IntoIterator<Item = &'a str>>(&self, paths: I) -> Vec<String> {
+        let mut reviewers = Vec::<String>::new();
+        for path in paths {
+            for owner in self.owners(path) {
+                if !owner.starts_with('@') {
+                    continue;
+                }
+                let owner = owner[1..].to_string();
+                if !reviewers.contains(&owner) {
+                    reviewers.push(owner);
+                }
+            }
+        }
+        reviewers
+    }
+}
+
+/// Splits reviewers into users and the slugs of teams, which are given as `org/team`.
+pub fn split_team_reviewers(reviewers: &[String]) -> (Vec<String>, Vec<String>) {
+    let (teams, users): (Vec<&String>, Vec<&String>) =
+        reviewers.iter().partition(|reviewer| reviewer.contains('/'));
+    (
+        users.into_iter().cloned().collect(),
+        teams
+            .into_iter()
+            .map(|team| team.rsplit('/').next().unwrap_or(team).to_string())
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODEOWNERS_FIXTURE: &str = indoc!(
+        "
+        # Everything not owned below.
+        *                   @alice
+        *.rs                @bob @Coneko/rustaceans
+        /docs/              docs@example.com
+        src/github.rs       @carol # Inline comment
+        apps/*              @dave
+        **/fixtures         @erin
+        /vendor/
+        "
+    );
+
+    fn owners(path: &str) -> Vec<String> {
+        CodeOwners::new_from_string(CODEOWNERS_FIXTURE)
+            .unwrap()
+            .owners(path)
+            .to_vec()
+    }
+
+    #[test]
+    fn owners_can_match_any_path() {
+        assert_eq!(owners("README.md"), vec!["@alice"]);
+    }
+
+    #[test]
+    fn owners_can_match_extension_at_any_depth() {
+        assert_eq!(owners("src/lib.rs"), vec!["@bob", "@Coneko/rustaceans"]);
+    }
+
+    #[test]
+    fn owners_can_match_directory() {
+        assert_eq!(owners("docs/guide/index.md"), vec!["docs@example.com"]);
+    }
+
+    #[test]
+    fn owners_can_match_anchored_path() {
+        assert_eq!(owners("src/github.rs"), vec!["@carol"]);
+        assert_eq!(owners("lib/src/github.rs"), vec!["@bob", "@Coneko/rustaceans"]);
+    }
+
+    #[test]
+    fn owners_cannot_match_nested_files_with_single_star() {
+        assert_eq!(owners("apps/web.js"), vec!["@dave"]);
+        assert_eq!(owners("apps/web/index.js"), vec!["@alice"]);
+    }
+
+    #[test]
+    fn owners_can_match_double_star_prefix() {
+        assert_eq!(owners("tests/fixtures/data.json"), vec!["@erin"]);
+    }
+
+    #[test]
+    fn owners_can_unset_owners() {
+        assert!(owners("vendor/lib.c").is_empty());
+    }
+
+    #[test]
+    fn reviewers_can_merge_owners_of_paths() {
+        let codeowners = CodeOwners::new_from_string(CODEOWNERS_FIXTURE).unwrap();
+        let result = codeowners.reviewers(vec!["src/lib.rs", "src/main.rs", "README.md"]);
+        assert_eq!(result, vec!["bob", "Coneko/rustaceans", "alice"]);
+    }
+
+    #[test]
+    fn reviewers_cannot_include_emails() {
+        let codeowners = CodeOwners::new_from_string(CODEOWNERS_FIXTURE).unwrap();
+        assert!(codeowners.reviewers(vec!["docs/index.md"]).is_empty());
+    }
+
+    #[test]
+    fn split_team_reviewers_can_split_teams() {
+        let reviewers = vec!["bob".to_string(), "Coneko/rustaceans".to_string()];
+        assert_eq!(
+            split_team_reviewers(&reviewers),
+            (vec!["bob".to_string()], vec!["rustaceans".to_string()])
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use errors::*;
+use git2;
+use std;
+use std::io::Read;
+use toml;
+
+/// Repository wide settings read from the `.stack.toml` file at the root of the work tree. Every
+/// key is optional, command line flags and git config take precedence over them.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub branch_prefix: Option<String>,
+    pub remote: Option<String>,
+    pub github_host: Option<String>,
+    #[serde(default)]
+    pub default_reviewers: Vec<String>,
+    pub draft: Option<bool>,
+}
+
+impl Config {
+    pub const FILE_NAME: &'static str = ".stack.toml";
+
+    /// Loads the config file of the repository, a missing file is the same as an empty one.
+    pub fn load(repo: &git2::Repository) -> Result<Config> {
+        let path = match repo.workdir() {
+            Some(workdir) => workdir.join(Self::FILE_NAME),
+            None => return Ok(Config::default()),
+        };
+        let mut file = match std::fs::File::open(&path) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Config::default())
+            }
+            Err(e) => {
+                return Err(e).chain_err(|| format!("Could not open '{}'.", path.display()))
+            }
+        };
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)
+            .chain_err(|| format!("Could not read '{}'.", path.display()))?;
+        Self::new_from_string(&contents).chain_err(|| format!("Invalid '{}'.", path.display()))
+    }
+
+    pub fn new_from_string(string: &str) -> Result<Config> {
+        toml::from_str(string).chain_err(|| "Could not parse stack config.")
+    }
+
+    pub fn remote(&self) -> &str {
+        self.remote.as_ref().map(|remote| remote.as_str()).unwrap_or("origin")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_from_string_can_parse_empty_string() {
+        let result = Config::new_from_string("");
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), Config::default());
+    }
+
+    #[test]
+    fn new_from_string_can_parse_all_keys() {
+        let result = Config::new_from_string(indoc!(
+            "
+            branch_prefix = \"alice/\"
+            remote = \"upstream\"
+            github_host = \"github.example.com\"
+            default_reviewers = [\"bob\", \"carol\"]
+            draft = true
+            "
+        ));
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap(),
+            Config {
+                branch_prefix: Some("alice/".to_string()),
+                remote: Some("upstream".to_string()),
+                github_host: Some("github.example.com".to_string()),
+                default_reviewers: vec!["bob".to_string(), "carol".to_string()],
+                draft: Some(true),
+            }
+        );
+    }
+
+    #[test]
+    fn new_from_string_cannot_parse_unknown_key() {
+        let result = Config::new_from_string("branch_prefixes = \"alice/\"");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn remote_defaults_to_origin() {
+        assert_eq!(Config::default().remote(), "origin");
+    }
+}
@@ -1 +1,50 @@
-error_chain!{}
+error_chain!{
+    foreign_links {
+        ChangesetParse(::changeset::ChangesetParseError);
+    }
+
+    errors {
+        Transient(description: String) {
+            description("transient failure")
+            display("{}", description)
+        }
+        Timeout(description: String) {
+            description("timed out")
+            display("{}", description)
+        }
+        PullRequestExists(description: String) {
+            description("pull request already exists")
+            display("{}", description)
+        }
+    }
+}
+
+impl Error {
+    /// Network failures and server errors might not happen again, so the failed operation can be
+    /// retried.
+    pub fn is_transient(&self) -> bool {
+        match *self.kind() {
+            ErrorKind::Transient(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Creating a pull request failed because one from the same head is already open.
+    pub fn is_pull_request_exists(&self) -> bool {
+        match *self.kind() {
+            ErrorKind::PullRequestExists(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Adds `description` to the error chain, keeping the kind of error it was.
+    pub fn context<S: Into<String>>(self, description: S) -> Error {
+        if self.is_transient() {
+            Error::with_chain(self, ErrorKind::Transient(description.into()))
+        } else if self.is_pull_request_exists() {
+            Error::with_chain(self, ErrorKind::PullRequestExists(description.into()))
+        } else {
+            Error::with_chain(self, description.into())
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use errors::*;
+use futures::Future;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A pull request, or a merge request on GitLab.
+#[derive(Debug)]
+pub struct PullRequest {
+    pub number: u64,
+    pub url: String,
+    pub title: String,
+    pub head: String,
+    pub base: String,
+    /// The commit the head branch is at.
+    pub head_sha: String,
+    /// The commit the base branch is at, GitLab only tells for a single merge request.
+    pub base_sha: Option<String>,
+    /// `open`, `closed`, or on GitLab also `merged`.
+    pub state: String,
+    /// Whether it can be merged, `None` while the forge is still checking or when it only tells
+    /// for a single pull request.
+    pub mergeable: Option<bool>,
+}
+
+pub struct NewPullRequest<'a> {
+    pub title: &'a str,
+    pub body: Option<&'a str>,
+    pub head: &'a str,
+    pub base: &'a str,
+    pub draft: bool,
+}
+
+/// How a pull request is merged into its base.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MergeMethod {
+    Merge,
+    Squash,
+    Rebase,
+}
+
+impl MergeMethod {
+    pub fn from_name(name: &str) -> Option<MergeMethod> {
+        match name {
+            "merge" => Some(MergeMethod::Merge),
+            "squash" => Some(MergeMethod::Squash),
+            "rebase" => Some(MergeMethod::Rebase),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            MergeMethod::Merge => "merge",
+            MergeMethod::Squash => "squash",
+            MergeMethod::Rebase => "rebase",
+        }
+    }
+}
+
+/// The API requests left before the forge refuses them until the limit resets.
+#[derive(Debug)]
+pub struct RateLimit {
+    pub limit: u32,
+    pub remaining: u32,
+    /// When `remaining` is back at `limit`, in seconds since the Unix epoch.
+    pub reset: u64,
+}
+
+impl RateLimit {
+    /// The time left until the limit resets as of `now`, zero once it has.
+    pub fn reset_in(&self, now: SystemTime) -> Duration {
+        let now = now.duration_since(UNIX_EPOCH)
+            .map(|now| now.as_secs())
+            .unwrap_or(0);
+        Duration::from_secs(self.reset.saturating_sub(now))
+    }
+
+    /// Describes when the limit resets, like `at 14:03:20 UTC, in 12m 5s`.
+    pub fn describe_reset(&self, now: SystemTime) -> String {
+        let time = self.reset % (24 * 60 * 60);
+        format!(
+            "at {:02}:{:02}:{:02} UTC, in {}",
+            time / (60 * 60),
+            time / 60 % 60,
+            time % 60,
+            format_duration(self.reset_in(now))
+        )
+    }
+}
+
+/// Formats `duration` like `1h 2m 3s`, leaving out the leading units that are zero.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match (secs / (60 * 60), secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {}s", m, s),
+        (h, m, s) => format!("{}h {}m {}s", h, m, s),
+    }
+}
+
+/// A code hosting service the stack's pull requests are opened on.
+pub trait Forge {
+    /// Checks that the token can access the repository, so that a run that couldn't open its pull
+    /// requests fails before pushing anything.
+    fn check_access(&self) -> Box<Future<Item = (), Error = Error>>;
+
+    /// The API rate limit of the token, `None` if the forge doesn't report one.
+    fn rate_limit(&self) -> Box<Future<Item = Option<RateLimit>, Error = Error>>;
+
+    /// The branch pull requests go to by default, if the repository has any branches yet.
+    fn default_branch(&self) -> Box<Future<Item = Option<String>, Error = Error>>;
+
+    fn get_pull_request(&self, number: u64) -> Box<Future<Item = PullRequest, Error = Error>>;
+
+    /// Finds the open pull request from `head`, given as `owner:branch` when the branch is in
+    /// another repository than the pull request.
+    fn find_pull_request(
+        &self,
+        head: &str,
+    ) -> Box<Future<Item = Option<PullRequest>, Error = Error>>;
+
+    /// Finds a merged pull request from `head`, as opposed to one closed without merging.
+    fn find_merged_pull_request(
+        &self,
+        head: &str,
+    ) -> Box<Future<Item = Option<PullRequest>, Error = Error>>;
+
+    fn list_open_pull_requests(&self) -> Box<Future<Item = Vec<PullRequest>, Error = Error>>;
+
+    fn create_pull_request(
+        &self,
+        pull_request: &NewPullRequest,
+    ) -> Box<Future<Item = PullRequest, Error = Error>>;
+
+    fn update_pull_request(
+        &self,
+        number: u64,
+        title: &str,
+        body: Option<&str>,
+    ) -> Box<Future<Item = PullRequest, Error = Error>>;
+
+    fn update_pull_request_base(
+        &self,
+        number: u64,
+        base: &str,
+    ) -> Box<Future<Item = PullRequest, Error = Error>>;
+
+    /// Requests reviews from the `reviewers` users and the teams with the `team_reviewers` slugs.
+    fn request_reviewers(
+        &self,
+        number: u64,
+        reviewers: &[String],
+        team_reviewers: &[String],
+    ) -> Box<Future<Item = (), Error = Error>>;
+
+    fn add_assignees(
+        &self,
+        number: u64,
+        assignees: &[String],
+    ) -> Box<Future<Item = (), Error = Error>>;
+
+    fn set_labels(&self, number: u64, labels: &[String]) -> Box<Future<Item = (), Error = Error>>;
+
+    /// Sets the milestone titled `milestone`, which has to exist already.
+    fn set_milestone(&self, number: u64, milestone: &str) -> Box<Future<Item = (), Error = Error>>;
+
+    /// Closes the pull request without merging it.
+    fn close_pull_request(&self, number: u64) -> Box<Future<Item = (), Error = Error>>;
+
+    /// Merges the pull request, unless its head was moved away from `sha` in the meantime.
+    fn merge_pull_request(
+        &self,
+        number: u64,
+        sha: &str,
+        method: MergeMethod,
+    ) -> Box<Future<Item = (), Error = Error>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate_limit(reset: u64) -> RateLimit {
+        RateLimit {
+            limit: 5000,
+            remaining: 0,
+            reset,
+        }
+    }
+
+    #[test]
+    fn format_duration_can_format_seconds() {
+        assert_eq!(format_duration(Duration::from_secs(5)), "5s");
+    }
+
+    #[test]
+    fn format_duration_can_format_minutes() {
+        assert_eq!(format_duration(Duration::from_secs(12 * 60 + 5)), "12m 5s");
+    }
+
+    #[test]
+    fn format_duration_can_format_hours() {
+        assert_eq!(format_duration(Duration::from_secs(60 * 60 + 2 * 60)), "1h 2m 0s");
+    }
+
+    #[test]
+    fn reset_in_is_zero_after_reset() {
+        let now = UNIX_EPOCH + Duration::from_secs(100);
+        assert_eq!(rate_limit(90).reset_in(now), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn describe_reset_can_describe_reset() {
+        let now = UNIX_EPOCH + Duration::from_secs(86_400 + 14 * 60 * 60);
+        let result = rate_limit(86_400 + 14 * 60 * 60 + 3 * 60 + 20).describe_reset(now);
+        assert_eq!(result, "at 14:03:20 UTC, in 3m 20s");
+    }
+
+    #[test]
+    fn merge_method_can_round_trip_names() {
+        for method in &[MergeMethod::Merge, MergeMethod::Squash, MergeMethod::Rebase] {
+            assert_eq!(MergeMethod::from_name(method.name()), Some(*method));
+        }
+        assert_eq!(MergeMethod::from_name("fast-forward"), None);
+    }
+}
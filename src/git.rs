@@ -0,0 +1,310 @@
+use errors::*;
+use git2;
+use std;
+use std::os::raw::c_int;
+
+/// The commits between `onto` and `head`, oldest first, so each one is uploaded after its parent.
+pub fn stack_commits<'repo>(
+    repo: &'repo git2::Repository,
+    onto: &str,
+    head: &git2::Commit<'repo>,
+) -> Result<Vec<git2::Commit<'repo>>> {
+    let onto_commit = repo.revparse_single(onto)
+        .chain_err(|| format!("Could not find base ref '{}'.", onto))?
+        .peel_to_commit()
+        .chain_err(|| format!("Base ref '{}' does not point to a commit.", onto))?;
+    let mut revwalk = repo.revwalk().chain_err(|| "Could not create revision walker.")?;
+    revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE);
+    revwalk
+        .push(head.id())
+        .chain_err(|| format!("Could not walk history from '{}'.", head.id()))?;
+    revwalk
+        .hide(onto_commit.id())
+        .chain_err(|| format!("Could not exclude history of '{}'.", onto))?;
+    let mut commits = Vec::new();
+    for id in revwalk {
+        let id = id.chain_err(|| "Could not walk the stack history.")?;
+        commits.push(repo.find_commit(id)
+            .chain_err(|| format!("Could not find commit '{}'.", id))?);
+    }
+    if commits.is_empty() {
+        bail!("No commits found between '{}' and HEAD.", onto);
+    }
+    Ok(commits)
+}
+
+/// Points the local branch `name` at `commit`. A branch that already points elsewhere is only
+/// moved when `overwrite` is set, as it may hold work that isn't anywhere else.
+pub fn stack_branch<'repo>(
+    repo: &'repo git2::Repository,
+    name: &str,
+    commit: &git2::Commit<'repo>,
+    overwrite: bool,
+) -> Result<git2::Branch<'repo>> {
+    if let Ok(branch) = repo.find_branch(name, git2::BranchType::Local) {
+        match branch.get().target() {
+            Some(target) if target == commit.id() => return Ok(branch),
+            Some(target) if !overwrite => bail!(
+                "Branch '{}' already points at '{}' instead of '{}', pass --force to move it.",
+                name,
+                target,
+                commit.id()
+            ),
+            _ => {}
+        }
+    }
+    repo.branch(name, commit, true)
+        .chain_err(|| format!("Could not create branch '{}'.", name))
+}
+
+/// The branch of `remote` its `HEAD` pointed at when it was last fetched or cloned.
+pub fn remote_head(repo: &git2::Repository, remote: &str) -> Option<String> {
+    let prefix = format!("refs/remotes/{}/", remote);
+    repo.find_reference(&format!("{}HEAD", prefix))
+        .ok()
+        .and_then(|head| head.symbolic_target().map(|target| target.to_string()))
+        .filter(|target| target.starts_with(&prefix))
+        .map(|target| target[prefix.len()..].to_string())
+}
+
+/// The name and tip of the default branch of `remote`, which is where its `HEAD` points, or else
+/// its `main` or `master` branch.
+pub fn default_branch(repo: &git2::Repository, remote: &str) -> Option<(String, git2::Oid)> {
+    let candidates = remote_head(repo, remote)
+        .map(|name| format!("refs/remotes/{}/{}", remote, name))
+        .into_iter()
+        .chain(vec![
+            format!("refs/remotes/{}/main", remote),
+            format!("refs/remotes/{}/master", remote),
+        ]);
+    for name in candidates {
+        if let Ok(id) = repo.refname_to_id(&name) {
+            let short_name = name.trim_left_matches("refs/remotes/").to_string();
+            return Some((short_name, id));
+        }
+    }
+    None
+}
+
+/// The branches of `remote` whose tracking branches are at `id`, its default branch first.
+pub fn remote_branches_at(repo: &git2::Repository, remote: &str, id: git2::Oid) -> Vec<String> {
+    let prefix = format!("{}/", remote);
+    let mut names = Vec::new();
+    if let Ok(branches) = repo.branches(Some(git2::BranchType::Remote)) {
+        for (branch, _) in branches.filter_map(|branch| branch.ok()) {
+            if branch.get().target() != Some(id) {
+                continue;
+            }
+            if let Ok(Some(name)) = branch.name() {
+                if name.starts_with(&prefix) && name != format!("{}HEAD", prefix) {
+                    names.push(name[prefix.len()..].to_string());
+                }
+            }
+        }
+    }
+    if let Some((default_name, _)) = default_branch(repo, remote) {
+        let default_name = default_name[prefix.len()..].to_string();
+        if let Some(index) = names.iter().position(|name| *name == default_name) {
+            let name = names.remove(index);
+            names.insert(0, name);
+        }
+    }
+    names
+}
+
+/// Points the local branch `name` at `commit` like `stack_branch`, and returns the refspec pushing
+/// it. The push is forced when `overwrite` is set, so the remote branch moves along.
+pub fn branch_refspec(
+    repo: &git2::Repository,
+    name: &str,
+    commit: &git2::Commit,
+    overwrite: bool,
+) -> Result<String> {
+    let branch = stack_branch(repo, name, commit, overwrite)
+        .chain_err(|| format!("Could not create branch at '{}'", commit.id()))?;
+    let reference = branch
+        .get()
+        .name()
+        .chain_err(|| format!("Branch '{}' has an invalid reference name.", name))?
+        .to_string();
+    Ok(format!("{}{}", if overwrite { "+" } else { "" }, reference))
+}
+
+/// Opens the repository containing `path` like git does, the worktree or submodule it's in rather
+/// than the repository around it.
+pub fn discover<P: AsRef<std::path::Path>>(path: P) -> Result<git2::Repository> {
+    let repo = git2::Repository::discover(path)
+        .chain_err(|| "Not a git repository (or any of the parent directories).")?;
+    if repo.is_worktree() && !common_dir(&repo).join("config").exists() {
+        bail!(
+            "The repository of worktree '{}' is missing at '{}'.",
+            repo.workdir().unwrap_or_else(|| repo.path()).display(),
+            common_dir(&repo).display()
+        );
+    }
+    Ok(repo)
+}
+
+/// The git directory shared by all worktrees of the repository, where its config and refs are.
+/// For a worktree it's the main repository's, as git2 only gives the worktree's own.
+pub fn common_dir(repo: &git2::Repository) -> std::path::PathBuf {
+    if !repo.is_worktree() {
+        return repo.path().to_path_buf();
+    }
+    match std::fs::read_to_string(repo.path().join("commondir")) {
+        Ok(common_dir) => repo.path().join(common_dir.trim()),
+        Err(_) => repo.path().to_path_buf(),
+    }
+}
+
+/// The working directory of the repository `repo` is a submodule of, if it's one.
+pub fn superproject(repo: &git2::Repository) -> Option<std::path::PathBuf> {
+    let workdir = repo.workdir()?;
+    let superproject = git2::Repository::discover(workdir.parent()?).ok()?;
+    let super_workdir = superproject.workdir()?.canonicalize().ok()?;
+    let path = workdir.canonicalize().ok()?;
+    let relative_path = path.strip_prefix(&super_workdir).ok()?;
+    superproject
+        .find_submodule(relative_path.to_str()?)
+        .ok()
+        .map(|_| super_workdir.to_path_buf())
+}
+
+/// The branches on the remote and the commits they're at, connecting with `callbacks`.
+pub fn remote_branches(
+    remote: &mut git2::Remote,
+    callbacks: git2::RemoteCallbacks,
+) -> Result<std::collections::HashMap<String, git2::Oid>> {
+    let url = remote.url().unwrap_or("").to_string();
+    let connection = remote
+        .connect_auth(git2::Direction::Fetch, Some(callbacks), None)
+        .chain_err(|| format!("Could not connect to remote '{}'.", url))?;
+    let heads = connection
+        .list()
+        .chain_err(|| format!("Could not list branches on remote '{}'.", url))?;
+    Ok(heads
+        .iter()
+        .filter(|head| head.name().starts_with("refs/heads/"))
+        .map(|head| (head.name()["refs/heads/".len()..].to_string(), head.oid()))
+        .collect())
+}
+
+/// What of an ssh command, like the one in `GIT_SSH_COMMAND`, libgit2's own SSH transport can
+/// honour: the private keys given with `-i` or `-o IdentityFile`, and the other arguments, which
+/// it ignores.
+#[derive(Debug, Default, PartialEq)]
+pub struct SshCommand {
+    pub identity_files: Vec<String>,
+    pub ignored_args: Vec<String>,
+}
+
+impl SshCommand {
+    /// Parses `command` the way a shell would split it. Commands that don't run `ssh`, like
+    /// wrapper scripts, can't be emulated and give `None`.
+    pub fn parse(command: &str) -> Option<SshCommand> {
+        let words = split_shell_words(command)?;
+        let mut words = words.into_iter();
+        let program = words.next()?;
+        match std::path::Path::new(&program).file_stem() {
+            Some(stem) if stem == "ssh" => {}
+            _ => return None,
+        }
+        let mut ssh_command = SshCommand::default();
+        while let Some(word) = words.next() {
+            let option = match word.as_str() {
+                "-i" | "-o" => words.next().map(|value| (word.clone(), value)),
+                _ if word.starts_with("-i") || word.starts_with("-o") => {
+                    Some((word[..2].to_string(), word[2..].to_string()))
+                }
+                _ => None,
+            };
+            match option {
+                Some((ref flag, ref value)) if flag == "-i" => {
+                    ssh_command.identity_files.push(value.clone())
+                }
+                Some((ref flag, ref value)) if flag == "-o" => {
+                    let mut parts = value.splitn(2, &['=', ' '][..]);
+                    match (parts.next(), parts.next()) {
+                        (Some(key), Some(file)) if key.eq_ignore_ascii_case("IdentityFile") => {
+                            ssh_command.identity_files.push(file.trim().to_string())
+                        }
+                        _ => ssh_command.ignored_args.extend(vec![flag.clone(), value.clone()]),
+                    }
+                }
+                _ => ssh_command.ignored_args.push(word),
+            }
+        }
+        Some(ssh_command)
+    }
+}
+
+/// Splits `command` into words, honouring quotes and backslash escapes. Unterminated quotes give
+/// `None`.
+fn split_shell_words(command: &str) -> Option<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next()? {
+                        '\'' => break,
+                        c => word.push(c),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => word.push(chars.next()?),
+                        c => word.push(c),
+                    }
+                }
+            }
+            '\\' => word.get_or_insert_with(String::new).push(chars.next()?),
+            c if c.is_whitespace() => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Some(words)
+}
+
+// The `git_feature_t` flags of libgit2's `common.h`.
+const GIT_FEATURE_THREADS: c_int = 1 << 0;
+const GIT_FEATURE_HTTPS: c_int = 1 << 1;
+const GIT_FEATURE_SSH: c_int = 1 << 2;
+
+// libgit2-sys 0.7, which git2 is built on, links libgit2 but doesn't bind these yet.
+extern "C" {
+    fn git_libgit2_version(major: *mut c_int, minor: *mut c_int, rev: *mut c_int);
+    fn git_libgit2_features() -> c_int;
+}
+
+/// The version of the linked libgit2 and the optional features it was built with, which git2
+/// doesn't expose.
+pub fn libgit2_version() -> (String, Vec<&'static str>) {
+    const FEATURES: &[(c_int, &str)] = &[
+        (GIT_FEATURE_THREADS, "threads"),
+        (GIT_FEATURE_HTTPS, "https"),
+        (GIT_FEATURE_SSH, "ssh"),
+    ];
+    let (mut major, mut minor, mut rev) = (0, 0, 0);
+    // Both only read constants compiled into libgit2, it doesn't even need to be initialized.
+    let features = unsafe {
+        git_libgit2_version(&mut major, &mut minor, &mut rev);
+        git_libgit2_features()
+    };
+    (
+        format!("{}.{}.{}", major, minor, rev),
+        FEATURES
+            .iter()
+            .filter(|&&(flag, _)| features & flag != 0)
+            .map(|&(_, name)| name)
+            .collect(),
+    )
+}
@@ -0,0 +1,950 @@
+use errors::*;
+use forge;
+use futures::{future, Future, Stream};
+use hubcaps;
+use hyper;
+use hyper_tls;
+use regex;
+use serde;
+use serde_json;
+use std;
+use tokio_core;
+
+pub const API_HOST: &str = "https://api.github.com";
+pub const JSON_MEDIA_TYPE: &str = "application/vnd.github.v3+json";
+pub const DRAFT_MEDIA_TYPE: &str = "application/vnd.github.shadow-cat-preview+json";
+/// Start of the message of the validation error for a pull request whose head is already open.
+const PULL_REQUEST_EXISTS: &str = "A pull request already exists";
+
+/// Explains what to do about a token the API refused, as the API's own message rarely does.
+fn unauthorized_hint(sso_url: Option<&str>) -> String {
+    match sso_url {
+        Some(url) => format!(
+            "The organization requires SAML single sign-on, authorize the token at {}",
+            url
+        ),
+        None => "Make sure the token has the 'repo' scope and, if the organization uses SAML \
+                 single sign-on, that it is authorized for the organization in the token settings."
+            .to_string(),
+    }
+}
+
+/// Minimal Github API client for the endpoints that hubcaps doesn't cover.
+#[derive(Clone)]
+pub struct Client {
+    host: String,
+    agent: String,
+    token: String,
+    http: hyper::Client<Connector>,
+}
+
+impl Client {
+    pub fn new(
+        host: &str,
+        agent: &str,
+        token: &str,
+        handle: &tokio_core::reactor::Handle,
+    ) -> Result<Client> {
+        let connector = hyper_tls::HttpsConnector::new(4, handle)
+            .chain_err(|| "Could not create HTTPS connector.")?;
+        let http = hyper::Client::configure()
+            .connector(connector)
+            .keep_alive(true)
+            .build(handle);
+        Ok(Client {
+            host: host.to_string(),
+            agent: agent.to_string(),
+            token: token.to_string(),
+            http,
+        })
+    }
+
+    pub fn request<D>(
+        &self,
+        method: hyper::Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+        media_type: &str,
+    ) -> Box<Future<Item = D, Error = Error>>
+    where
+        D: serde::de::DeserializeOwned + 'static,
+    {
+        let url = format!("{}{}", self.host, path);
+        debug!("{} {}", method, url);
+        let uri = match url.parse::<hyper::Uri>() {
+            Ok(uri) => uri,
+            Err(e) => {
+                return Box::new(future::err(Error::with_chain(
+                    e,
+                    format!("Invalid Github API url '{}'.", url),
+                )))
+            }
+        };
+        let mime = match media_type.parse::<hyper::mime::Mime>() {
+            Ok(mime) => mime,
+            Err(e) => {
+                return Box::new(future::err(Error::with_chain(
+                    e,
+                    format!("Invalid media type '{}'.", media_type),
+                )))
+            }
+        };
+        let mut request = hyper::Request::new(method, uri);
+        {
+            let headers = request.headers_mut();
+            headers.set(hyper::header::UserAgent::new(self.agent.clone()));
+            headers.set(hyper::header::Accept(vec![hyper::header::qitem(mime)]));
+            headers.set(hyper::header::Authorization(format!("token {}", self.token)));
+        }
+        if let Some(body) = body {
+            request.headers_mut().set(hyper::header::ContentType::json());
+            request.set_body(body.to_string());
+        }
+        Box::new(
+            self.http
+                .request(request)
+                .map_err(move |e| {
+                    Error::with_chain(
+                        e,
+                        ErrorKind::Transient(format!("Request to '{}' failed.", url)),
+                    )
+                })
+                .and_then(|response| {
+                    let status = response.status();
+                    let rate_limit_reset = Self::rate_limit_reset(response.headers());
+                    // The header looks like `required; url=<authorization url>`.
+                    let sso_url = response
+                        .headers()
+                        .get_raw("X-GitHub-SSO")
+                        .and_then(|value| value.one())
+                        .map(|value| String::from_utf8_lossy(value).into_owned())
+                        .and_then(|value| {
+                            value
+                                .find("url=")
+                                .map(|start| value[start + "url=".len()..].to_string())
+                        });
+                    response
+                        .body()
+                        .concat2()
+                        .map_err(|e| {
+                            Error::with_chain(
+                                e,
+                                ErrorKind::Transient(
+                                    "Could not read Github API response.".to_string(),
+                                ),
+                            )
+                        })
+                        .and_then(move |body| {
+                            if status.is_server_error() {
+                                bail!(ErrorKind::Transient(format!(
+                                    "Github API request failed with status '{}': {}",
+                                    status,
+                                    String::from_utf8_lossy(&body)
+                                )));
+                            }
+                            if let (hyper::StatusCode::Forbidden, Some(reset)) =
+                                (status, rate_limit_reset)
+                            {
+                                bail!(
+                                    "Github API rate limit exceeded, it resets {}.",
+                                    reset.describe_reset(std::time::SystemTime::now())
+                                );
+                            }
+                            if status == hyper::StatusCode::Unauthorized
+                                || status == hyper::StatusCode::Forbidden
+                            {
+                                bail!(
+                                    "Github refused the token with status '{}': {}\n{}",
+                                    status,
+                                    String::from_utf8_lossy(&body),
+                                    unauthorized_hint(sso_url.as_ref().map(|url| url.as_str()))
+                                );
+                            }
+                            // The only validation error told apart, as it's expected when a
+                            // pull request was opened by an interrupted run.
+                            if status == hyper::StatusCode::UnprocessableEntity
+                                && String::from_utf8_lossy(&body).contains(PULL_REQUEST_EXISTS)
+                            {
+                                bail!(ErrorKind::PullRequestExists(format!(
+                                    "Github API request failed with status '{}': {}",
+                                    status,
+                                    String::from_utf8_lossy(&body)
+                                )));
+                            }
+                            if !status.is_success() {
+                                bail!(
+                                    "Github API request failed with status '{}': {}",
+                                    status,
+                                    String::from_utf8_lossy(&body)
+                                );
+                            }
+                            serde_json::from_slice::<D>(&body)
+                                .chain_err(|| "Could not parse Github API response.")
+                        })
+                }),
+        )
+    }
+
+    /// The rate limit of a response that used up the last request, as it is reset then.
+    fn rate_limit_reset(headers: &hyper::Headers) -> Option<forge::RateLimit> {
+        let header = |name: &str| {
+            headers
+                .get_raw(name)
+                .and_then(|value| value.one())
+                .and_then(|value| String::from_utf8_lossy(value).parse::<u64>().ok())
+        };
+        match (
+            header("X-RateLimit-Limit"),
+            header("X-RateLimit-Remaining"),
+            header("X-RateLimit-Reset"),
+        ) {
+            (Some(limit), Some(0), Some(reset)) => Some(forge::RateLimit {
+                limit: limit as u32,
+                remaining: 0,
+                reset,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn request_reviewers(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u64,
+        reviewers: &[String],
+        team_reviewers: &[String],
+    ) -> Box<Future<Item = (), Error = Error>> {
+        Box::new(
+            self.request::<serde_json::Value>(
+                hyper::Method::Post,
+                &format!("/repos/{}/{}/pulls/{}/requested_reviewers", owner, repo, number),
+                Some(json!({ "reviewers": reviewers, "team_reviewers": team_reviewers })),
+                JSON_MEDIA_TYPE,
+            ).map(|_| ()),
+        )
+    }
+}
+
+type Connector = hyper_tls::HttpsConnector<hyper::client::HttpConnector>;
+
+/// The Github forge, going through hubcaps where it supports the endpoint and `Client` otherwise.
+pub struct Github {
+    repo: Repo,
+    hubcaps_repo: hubcaps::repositories::Repository<Connector>,
+    client: Client,
+}
+
+impl Github {
+    pub fn new(
+        repo: Repo,
+        token: &str,
+        agent: &str,
+        handle: &tokio_core::reactor::Handle,
+    ) -> Result<Github> {
+        let api_url = repo.api_url();
+        let hubcaps_repo = hubcaps::Github::host(
+            api_url.as_str(),
+            agent,
+            Some(hubcaps::Credentials::Token(token.to_string())),
+            handle,
+        ).repo(repo.owner.as_str(), repo.name.as_str());
+        let client = Client::new(&api_url, agent, token, handle)?;
+        Ok(Github {
+            repo,
+            hubcaps_repo,
+            client,
+        })
+    }
+}
+
+fn hubcaps_error<S: Into<String>>(error: hubcaps::errors::Error, description: S) -> Error {
+    let exists = match *error.kind() {
+        hubcaps::errors::ErrorKind::Fault { ref error, .. } => error
+            .errors
+            .iter()
+            .flat_map(|errors| errors)
+            .filter_map(|error| error.message.as_ref())
+            .any(|message| message.starts_with(PULL_REQUEST_EXISTS)),
+        _ => false,
+    };
+    if exists {
+        return Error::with_chain(error, ErrorKind::PullRequestExists(description.into()));
+    }
+    if let hubcaps::errors::ErrorKind::RateLimit { reset } = *error.kind() {
+        let description = format!(
+            "{} The API rate limit is exceeded, it resets in {}.",
+            description.into(),
+            forge::format_duration(reset)
+        );
+        return Error::with_chain(error, description);
+    }
+    // hubcaps doesn't expose the headers that would tell single sign-on apart.
+    let unauthorized = match *error.kind() {
+        hubcaps::errors::ErrorKind::Fault { ref code, .. } => {
+            *code == hyper::StatusCode::Unauthorized || *code == hyper::StatusCode::Forbidden
+        }
+        _ => false,
+    };
+    if unauthorized {
+        let description = format!("{} {}", description.into(), unauthorized_hint(None));
+        return Error::with_chain(error, description);
+    }
+    let transient = match *error.kind() {
+        hubcaps::errors::ErrorKind::Http(_) | hubcaps::errors::ErrorKind::IO(_) => true,
+        hubcaps::errors::ErrorKind::Fault { ref code, .. } => code.is_server_error(),
+        _ => false,
+    };
+    if transient {
+        Error::with_chain(error, ErrorKind::Transient(description.into()))
+    } else {
+        Error::with_chain(error, description.into())
+    }
+}
+
+fn pull_request(pull: hubcaps::pulls::Pull) -> forge::PullRequest {
+    forge::PullRequest {
+        number: pull.number,
+        url: pull.html_url,
+        title: pull.title,
+        head: pull.head.commit_ref,
+        base: pull.base.commit_ref,
+        head_sha: pull.head.sha,
+        base_sha: Some(pull.base.sha),
+        state: pull.state,
+        mergeable: pull.mergeable,
+    }
+}
+
+impl forge::Forge for Github {
+    fn check_access(&self) -> Box<Future<Item = (), Error = Error>> {
+        let full_name = format!("{}/{}", self.repo.owner, self.repo.name);
+        Box::new(self.hubcaps_repo.get().map(|_| ()).map_err(move |e| {
+            // Private repositories look missing to a token without the 'repo' scope.
+            let not_found = match *e.kind() {
+                hubcaps::errors::ErrorKind::Fault { ref code, .. } => {
+                    *code == hyper::StatusCode::NotFound
+                }
+                _ => false,
+            };
+            if not_found {
+                hubcaps_error(
+                    e,
+                    format!(
+                        "Could not access repository {}. {}",
+                        full_name,
+                        unauthorized_hint(None)
+                    ),
+                )
+            } else {
+                hubcaps_error(e, format!("Could not access repository {}.", full_name))
+            }
+        }))
+    }
+
+    fn default_branch(&self) -> Box<Future<Item = Option<String>, Error = Error>> {
+        let full_name = format!("{}/{}", self.repo.owner, self.repo.name);
+        Box::new(
+            self.hubcaps_repo
+                .get()
+                .map(|repo| Some(repo.default_branch).filter(|name| !name.is_empty()))
+                .map_err(move |e| {
+                    hubcaps_error(e, format!("Could not get the default branch of {}.", full_name))
+                }),
+        )
+    }
+
+    fn rate_limit(&self) -> Box<Future<Item = Option<forge::RateLimit>, Error = Error>> {
+        // Asking for the rate limit doesn't count against it.
+        Box::new(
+            self.client
+                .request::<RateLimits>(hyper::Method::Get, "/rate_limit", None, JSON_MEDIA_TYPE)
+                .map(|limits| {
+                    let core = limits.resources.core;
+                    Some(forge::RateLimit {
+                        limit: core.limit,
+                        remaining: core.remaining,
+                        reset: core.reset,
+                    })
+                })
+                .map_err(|e| e.context("Could not get the API rate limit.")),
+        )
+    }
+
+    fn get_pull_request(
+        &self,
+        number: u64,
+    ) -> Box<Future<Item = forge::PullRequest, Error = Error>> {
+        debug!("Getting pull request #{} of {}/{}.", number, self.repo.owner, self.repo.name);
+        Box::new(
+            self.hubcaps_repo
+                .pulls()
+                .get(number)
+                .get()
+                .map(pull_request)
+                .map_err(move |e| {
+                    hubcaps_error(e, format!("Could not get pull request #{}.", number))
+                }),
+        )
+    }
+
+    fn find_pull_request(
+        &self,
+        head: &str,
+    ) -> Box<Future<Item = Option<forge::PullRequest>, Error = Error>> {
+        // The API filters on `owner:branch` and ignores the filter without the owner.
+        let head = if head.contains(':') {
+            head.to_string()
+        } else {
+            format!("{}:{}", self.repo.owner, head)
+        };
+        debug!("Finding pull request from '{}' in {}/{}.", head, self.repo.owner, self.repo.name);
+        Box::new(
+            self.client
+                .request::<Vec<hubcaps::pulls::Pull>>(
+                    hyper::Method::Get,
+                    &format!(
+                        "/repos/{}/{}/pulls?state=open&head={}",
+                        self.repo.owner, self.repo.name, head
+                    ),
+                    None,
+                    JSON_MEDIA_TYPE,
+                )
+                .map(|pulls| pulls.into_iter().next().map(pull_request))
+                .map_err(move |e| {
+                    e.context(format!("Could not find pull request from '{}'.", head))
+                }),
+        )
+    }
+
+    fn find_merged_pull_request(
+        &self,
+        head: &str,
+    ) -> Box<Future<Item = Option<forge::PullRequest>, Error = Error>> {
+        let head = if head.contains(':') {
+            head.to_string()
+        } else {
+            format!("{}:{}", self.repo.owner, head)
+        };
+        debug!(
+            "Finding merged pull request from '{}' in {}/{}.",
+            head, self.repo.owner, self.repo.name
+        );
+        Box::new(
+            self.client
+                .request::<Vec<hubcaps::pulls::Pull>>(
+                    hyper::Method::Get,
+                    &format!(
+                        "/repos/{}/{}/pulls?state=closed&head={}",
+                        self.repo.owner, self.repo.name, head
+                    ),
+                    None,
+                    JSON_MEDIA_TYPE,
+                )
+                // Closed pull requests that weren't merged have no merge time.
+                .map(|pulls| {
+                    pulls
+                        .into_iter()
+                        .find(|pull| pull.merged_at.is_some())
+                        .map(pull_request)
+                })
+                .map_err(move |e| {
+                    e.context(format!("Could not find pull request from '{}'.", head))
+                }),
+        )
+    }
+
+    fn list_open_pull_requests(
+        &self,
+    ) -> Box<Future<Item = Vec<forge::PullRequest>, Error = Error>> {
+        debug!("Listing open pull requests of {}/{}.", self.repo.owner, self.repo.name);
+        let pull_list_options = hubcaps::pulls::PullListOptions::builder()
+            .state(hubcaps::issues::State::Open)
+            .build();
+        Box::new(
+            self.hubcaps_repo
+                .pulls()
+                .iter(&pull_list_options)
+                .map(pull_request)
+                .collect()
+                .map_err(|e| hubcaps_error(e, "Could not list pull requests.")),
+        )
+    }
+
+    fn create_pull_request(
+        &self,
+        pull_request: &forge::NewPullRequest,
+    ) -> Box<Future<Item = forge::PullRequest, Error = Error>> {
+        let pull_options = hubcaps::pulls::PullOptions::new(
+            pull_request.title,
+            pull_request.head,
+            pull_request.base,
+            pull_request.body,
+        );
+        if !pull_request.draft {
+            debug!(
+                "Creating pull request from '{}' onto '{}' in {}/{}.",
+                pull_request.head, pull_request.base, self.repo.owner, self.repo.name
+            );
+            return Box::new(
+                self.hubcaps_repo
+                    .pulls()
+                    .create(&pull_options)
+                    .map(self::pull_request)
+                    .map_err(|e| hubcaps_error(e, "Could not create pull request.")),
+            );
+        }
+        // hubcaps has no support for draft pull requests, so this goes through the API directly
+        // with the draft preview media type.
+        let mut body = match serde_json::to_value(&pull_options) {
+            Ok(body) => body,
+            Err(e) => {
+                return Box::new(future::err(Error::with_chain(
+                    e,
+                    "Could not serialize pull request options.",
+                )))
+            }
+        };
+        body["draft"] = serde_json::Value::Bool(true);
+        Box::new(
+            self.client
+                .request::<hubcaps::pulls::Pull>(
+                    hyper::Method::Post,
+                    &format!("/repos/{}/{}/pulls", self.repo.owner, self.repo.name),
+                    Some(body),
+                    DRAFT_MEDIA_TYPE,
+                )
+                .map(self::pull_request)
+                .map_err(|e| e.context("Could not create draft pull request.")),
+        )
+    }
+
+    fn update_pull_request(
+        &self,
+        number: u64,
+        title: &str,
+        body: Option<&str>,
+    ) -> Box<Future<Item = forge::PullRequest, Error = Error>> {
+        let mut pull_edit_options = hubcaps::pulls::PullEditOptions::builder();
+        pull_edit_options.title(title);
+        if let Some(body) = body {
+            pull_edit_options.body(body);
+        }
+        debug!("Updating pull request #{} of {}/{}.", number, self.repo.owner, self.repo.name);
+        Box::new(
+            self.hubcaps_repo
+                .pulls()
+                .get(number)
+                .edit(&pull_edit_options.build())
+                .map(pull_request)
+                .map_err(move |e| {
+                    hubcaps_error(e, format!("Could not update pull request #{}.", number))
+                }),
+        )
+    }
+
+    fn update_pull_request_base(
+        &self,
+        number: u64,
+        base: &str,
+    ) -> Box<Future<Item = forge::PullRequest, Error = Error>> {
+        // hubcaps can't change the base of a pull request.
+        Box::new(
+            self.client
+                .request::<hubcaps::pulls::Pull>(
+                    hyper::Method::Patch,
+                    &format!(
+                        "/repos/{}/{}/pulls/{}",
+                        self.repo.owner, self.repo.name, number
+                    ),
+                    Some(json!({ "base": base })),
+                    JSON_MEDIA_TYPE,
+                )
+                .map(self::pull_request)
+                .map_err(move |e| {
+                    e.context(format!("Could not change the base of pull request #{}.", number))
+                }),
+        )
+    }
+
+    fn request_reviewers(
+        &self,
+        number: u64,
+        reviewers: &[String],
+        team_reviewers: &[String],
+    ) -> Box<Future<Item = (), Error = Error>> {
+        self.client.request_reviewers(
+            &self.repo.owner,
+            &self.repo.name,
+            number,
+            reviewers,
+            team_reviewers,
+        )
+    }
+
+    fn add_assignees(
+        &self,
+        number: u64,
+        assignees: &[String],
+    ) -> Box<Future<Item = (), Error = Error>> {
+        // hubcaps can only set a single assignee, and only by editing the whole issue.
+        Box::new(
+            self.client
+                .request::<serde_json::Value>(
+                    hyper::Method::Post,
+                    &format!(
+                        "/repos/{}/{}/issues/{}/assignees",
+                        self.repo.owner, self.repo.name, number
+                    ),
+                    Some(json!({ "assignees": assignees })),
+                    JSON_MEDIA_TYPE,
+                )
+                .map(|_| ())
+                .map_err(move |e| {
+                    e.context(format!("Could not assign pull request #{}.", number))
+                }),
+        )
+    }
+
+    fn set_labels(&self, number: u64, labels: &[String]) -> Box<Future<Item = (), Error = Error>> {
+        debug!("Setting labels of pull request #{} to {}.", number, labels.join(", "));
+        Box::new(
+            self.hubcaps_repo
+                .issue(number)
+                .labels()
+                .set(labels.iter().map(|label| label.as_str()).collect())
+                .map(|_| ())
+                .map_err(move |e| {
+                    hubcaps_error(e, format!("Could not set labels on pull request #{}.", number))
+                }),
+        )
+    }
+
+    fn set_milestone(&self, number: u64, milestone: &str) -> Box<Future<Item = (), Error = Error>> {
+        // hubcaps has no support for milestones, which are set by number.
+        debug!("Setting milestone of pull request #{} to '{}'.", number, milestone);
+        let milestones_path = format!(
+            "/repos/{}/{}/milestones?state=all&per_page=100",
+            self.repo.owner, self.repo.name
+        );
+        let issue_path = format!(
+            "/repos/{}/{}/issues/{}",
+            self.repo.owner, self.repo.name, number
+        );
+        let repo = format!("{}/{}", self.repo.owner, self.repo.name);
+        let milestone = milestone.to_string();
+        let client = self.client.clone();
+        Box::new(
+            self.client
+                .request::<Vec<Milestone>>(
+                    hyper::Method::Get,
+                    &milestones_path,
+                    None,
+                    JSON_MEDIA_TYPE,
+                )
+                .and_then(move |milestones| {
+                    match milestones.into_iter().find(|m| m.title == milestone) {
+                        Some(found) => Ok(found.number),
+                        None => bail!("Milestone '{}' does not exist on {}.", milestone, repo),
+                    }
+                })
+                .and_then(move |milestone_number| {
+                    client.request::<serde_json::Value>(
+                        hyper::Method::Patch,
+                        &issue_path,
+                        Some(json!({ "milestone": milestone_number })),
+                        JSON_MEDIA_TYPE,
+                    )
+                })
+                .map(|_| ())
+                .map_err(move |e| {
+                    e.context(format!("Could not set the milestone of pull request #{}.", number))
+                }),
+        )
+    }
+
+    fn close_pull_request(&self, number: u64) -> Box<Future<Item = (), Error = Error>> {
+        Box::new(
+            self.hubcaps_repo
+                .pulls()
+                .get(number)
+                .close()
+                .map(|_| ())
+                .map_err(move |e| {
+                    hubcaps_error(e, format!("Could not close pull request #{}.", number))
+                }),
+        )
+    }
+
+    fn merge_pull_request(
+        &self,
+        number: u64,
+        sha: &str,
+        method: forge::MergeMethod,
+    ) -> Box<Future<Item = (), Error = Error>> {
+        // hubcaps can't merge pull requests.
+        debug!("Merging pull request #{} at {} with {}.", number, sha, method.name());
+        Box::new(
+            self.client
+                .request::<serde_json::Value>(
+                    hyper::Method::Put,
+                    &format!(
+                        "/repos/{}/{}/pulls/{}/merge",
+                        self.repo.owner, self.repo.name, number
+                    ),
+                    Some(json!({ "sha": sha, "merge_method": method.name() })),
+                    JSON_MEDIA_TYPE,
+                )
+                .map(|_| ())
+                .map_err(move |e| e.context(format!("Could not merge pull request #{}.", number))),
+        )
+    }
+}
+
+#[derive(Deserialize)]
+struct Milestone {
+    number: u64,
+    title: String,
+}
+
+#[derive(Deserialize)]
+struct RateLimits {
+    resources: RateLimitResources,
+}
+
+/// The rate limits of the API by kind of request, only `core` applies to the endpoints used here.
+#[derive(Deserialize)]
+struct RateLimitResources {
+    core: RateLimitResource,
+}
+
+#[derive(Deserialize)]
+struct RateLimitResource {
+    limit: u32,
+    remaining: u32,
+    reset: u64,
+}
+
+/// A Github repository, identified by the host serving it and its owner and name.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Repo {
+    pub host: String,
+    pub owner: String,
+    pub name: String,
+    /// Overrides the API url derived from the host.
+    pub api_url: Option<String>,
+}
+
+impl Repo {
+    pub fn from_url(url: &str) -> Result<Repo> {
+        // The port of an ssh url is dropped, since the API is served over HTTPS anyway.
+        let re = regex::Regex::new(concat!(
+            r"^(git@(?P<scp_host>[^:/]+):|ssh://([^@/]+@)?(?P<ssh_host>[^/:]+)(:[0-9]+)?/|",
+            r"https?://([^@/]+@)?(?P<host>[^/:]+)/)(?P<owner>[^/]+)/(?P<repo>[^/]+?)(\.git)?/?$",
+        )).chain_err(|| "Could not construct Github repo regex.")?;
+        let captures = re
+            .captures(url)
+            .ok_or_else(|| format!("Could not extract Github repo from url '{}'.", url))?;
+        let host = captures
+            .name("scp_host")
+            .or_else(|| captures.name("ssh_host"))
+            .or_else(|| captures.name("host"))
+            .ok_or_else(|| format!("Could not find github host in url '{}'.", url))?
+            .as_str();
+        let owner = captures
+            .name("owner")
+            .ok_or_else(|| format!("Could not find github owner in url '{}'.", url))?
+            .as_str();
+        let name = captures
+            .name("repo")
+            .ok_or_else(|| format!("Could not find github repo in url '{}'.", url))?
+            .as_str();
+        Ok(Repo {
+            host: host.to_string(),
+            owner: owner.to_string(),
+            name: name.to_string(),
+            api_url: None,
+        })
+    }
+
+    /// Serves the repo from `api_url`, like the `GITHUB_API_URL` of a Github Actions run, the host
+    /// of its pull requests is derived from it.
+    pub fn set_api_url(&mut self, api_url: &str) -> Result<()> {
+        let re = regex::Regex::new(r"^https?://(?P<host>[^/:]+)(:[0-9]+)?(/.*)?$")
+            .chain_err(|| "Could not construct API url regex.")?;
+        let host = re
+            .captures(api_url)
+            .and_then(|captures| captures.name("host"))
+            .ok_or_else(|| format!("Could not find host in API url '{}'.", api_url))?
+            .as_str();
+        self.host = if host == "api.github.com" {
+            "github.com".to_string()
+        } else {
+            host.to_string()
+        };
+        self.api_url = Some(api_url.trim_right_matches('/').to_string());
+        Ok(())
+    }
+
+    /// Base url of the API serving this repo: api.github.com for github.com, the
+    /// `/api/v3` endpoint of the host for Github Enterprise.
+    pub fn api_url(&self) -> String {
+        if let Some(ref api_url) = self.api_url {
+            api_url.clone()
+        } else if self.host == "github.com" {
+            API_HOST.to_string()
+        } else {
+            format!("https://{}/api/v3", self.host)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo(host: &str) -> Repo {
+        Repo {
+            host: host.to_string(),
+            owner: "Coneko".to_string(),
+            name: "stack".to_string(),
+            api_url: None,
+        }
+    }
+
+    #[test]
+    fn from_url_cannot_parse_empty_string() {
+        let result = Repo::from_url("");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn from_url_cannot_parse_url_without_repo() {
+        let result = Repo::from_url("https://github.com/Coneko");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn from_url_can_parse_scp_url() {
+        let result = Repo::from_url("git@github.com:Coneko/stack.git");
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), repo("github.com"));
+    }
+
+    #[test]
+    fn from_url_can_parse_scp_url_without_suffix() {
+        let result = Repo::from_url("git@github.com:Coneko/stack");
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), repo("github.com"));
+    }
+
+    #[test]
+    fn from_url_can_parse_repo_name_with_dots() {
+        let result = Repo::from_url("git@github.com:Coneko/stack.rs.git");
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().name, "stack.rs");
+    }
+
+    #[test]
+    fn from_url_can_parse_repo_name_with_dots_without_suffix() {
+        let result = Repo::from_url("https://github.com/Coneko/stack.rs");
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().name, "stack.rs");
+    }
+
+    #[test]
+    fn from_url_can_parse_https_url() {
+        let result = Repo::from_url("https://github.com/Coneko/stack.git");
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), repo("github.com"));
+    }
+
+    #[test]
+    fn from_url_can_parse_https_url_without_suffix() {
+        let result = Repo::from_url("https://github.com/Coneko/stack");
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), repo("github.com"));
+    }
+
+    #[test]
+    fn from_url_can_parse_ssh_url() {
+        let result = Repo::from_url("ssh://git@github.com/Coneko/stack");
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), repo("github.com"));
+    }
+
+    #[test]
+    fn from_url_can_parse_ssh_url_with_suffix() {
+        let result = Repo::from_url("ssh://git@github.com/Coneko/stack.git");
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), repo("github.com"));
+    }
+
+    #[test]
+    fn from_url_can_parse_ssh_url_with_port() {
+        let result = Repo::from_url("ssh://git@github.com:22/Coneko/stack.git");
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), repo("github.com"));
+    }
+
+    #[test]
+    fn from_url_can_parse_enterprise_ssh_url_with_port() {
+        let result = Repo::from_url("ssh://git@github.example.com:7999/Coneko/stack");
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), repo("github.example.com"));
+    }
+
+    #[test]
+    fn from_url_can_parse_enterprise_scp_url() {
+        let result = Repo::from_url("git@github.example.com:Coneko/stack.git");
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), repo("github.example.com"));
+    }
+
+    #[test]
+    fn from_url_can_parse_enterprise_https_url() {
+        let result = Repo::from_url("https://user@github.example.com/Coneko/stack.git");
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), repo("github.example.com"));
+    }
+
+    #[test]
+    fn api_url_uses_api_host_for_github() {
+        assert_eq!(repo("github.com").api_url(), "https://api.github.com");
+    }
+
+    #[test]
+    fn api_url_uses_api_v3_for_enterprise() {
+        assert_eq!(
+            repo("github.example.com").api_url(),
+            "https://github.example.com/api/v3"
+        );
+    }
+
+    #[test]
+    fn set_api_url_can_set_github_api_url() {
+        let mut result = repo("github.example.com");
+        assert!(result.set_api_url("https://api.github.com").is_ok());
+        assert_eq!(result.host, "github.com");
+        assert_eq!(result.api_url(), "https://api.github.com");
+    }
+
+    #[test]
+    fn set_api_url_can_set_enterprise_api_url() {
+        let mut result = repo("github.com");
+        assert!(
+            result
+                .set_api_url("https://github.example.com:8443/api/v3/")
+                .is_ok()
+        );
+        assert_eq!(result.host, "github.example.com");
+        assert_eq!(result.api_url(), "https://github.example.com:8443/api/v3");
+    }
+
+    #[test]
+    fn set_api_url_cannot_set_url_without_host() {
+        let mut result = repo("github.com");
+        assert!(result.set_api_url("api/v3").is_err());
+        assert_eq!(result, repo("github.com"));
+    }
+}
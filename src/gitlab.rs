@@ -0,0 +1,569 @@
+use errors::*;
+use forge;
+use futures::{future, Future, Stream};
+use hyper;
+use hyper_tls;
+use serde;
+use serde_json;
+use tokio_core;
+use url;
+
+header! { (PrivateToken, "PRIVATE-TOKEN") => [String] }
+
+/// Encodes the `pairs` as a query string, branch names and titles can contain `&`, `#` or `+`.
+fn query(pairs: &[(&str, &str)]) -> String {
+    url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(pairs)
+        .finish()
+}
+
+/// Only included for a single merge request, `start_sha` is where its target branch is at.
+#[derive(Deserialize)]
+struct DiffRefs {
+    start_sha: String,
+}
+
+#[derive(Deserialize)]
+struct User {
+    id: u64,
+}
+
+#[derive(Deserialize)]
+struct Milestone {
+    id: u64,
+}
+
+#[derive(Deserialize)]
+struct Project {
+    /// Missing while the project has no branches.
+    #[serde(default)]
+    default_branch: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct MergeRequest {
+    iid: u64,
+    web_url: String,
+    title: String,
+    source_branch: String,
+    target_branch: String,
+    /// Missing while the source branch has no commits.
+    #[serde(default)]
+    sha: Option<String>,
+    state: String,
+    #[serde(default)]
+    diff_refs: Option<DiffRefs>,
+    #[serde(default)]
+    merge_status: Option<String>,
+}
+
+impl From<MergeRequest> for forge::PullRequest {
+    fn from(merge_request: MergeRequest) -> forge::PullRequest {
+        forge::PullRequest {
+            number: merge_request.iid,
+            url: merge_request.web_url,
+            title: merge_request.title,
+            head: merge_request.source_branch,
+            base: merge_request.target_branch,
+            head_sha: merge_request.sha.unwrap_or_default(),
+            base_sha: merge_request.diff_refs.map(|diff_refs| diff_refs.start_sha),
+            state: match merge_request.state.as_str() {
+                "opened" => "open".to_string(),
+                _ => merge_request.state,
+            },
+            // Anything else means GitLab hasn't checked yet.
+            mergeable: match merge_request.merge_status.as_ref().map(|s| s.as_str()) {
+                Some("can_be_merged") => Some(true),
+                Some("cannot_be_merged") => Some(false),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// The GitLab forge, where pull requests are called merge requests.
+#[derive(Clone)]
+pub struct Gitlab {
+    api_url: String,
+    project: String,
+    token: String,
+    http: hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>,
+}
+
+impl Gitlab {
+    pub fn new(
+        host: &str,
+        owner: &str,
+        name: &str,
+        token: &str,
+        handle: &tokio_core::reactor::Handle,
+    ) -> Result<Gitlab> {
+        let connector = hyper_tls::HttpsConnector::new(4, handle)
+            .chain_err(|| "Could not create HTTPS connector.")?;
+        let http = hyper::Client::configure()
+            .connector(connector)
+            .keep_alive(true)
+            .build(handle);
+        Ok(Gitlab {
+            api_url: format!("https://{}/api/v4", host),
+            // Projects are addressed by their url-encoded path.
+            project: format!("{}%2F{}", owner, name),
+            token: token.to_string(),
+            http,
+        })
+    }
+
+    /// Looks up the ids of the users with the `usernames`, which merge requests refer to them by.
+    fn user_ids(&self, usernames: &[String]) -> Box<Future<Item = Vec<u64>, Error = Error>> {
+        let users = usernames
+            .iter()
+            .map(|username| {
+                let username = username.clone();
+                self.request_url::<Vec<User>>(
+                    hyper::Method::Get,
+                    format!("{}/users?{}", self.api_url, query(&[("username", &username)])),
+                    None,
+                ).and_then(move |users| match users.into_iter().next() {
+                    Some(user) => Ok(user.id),
+                    None => bail!("Could not find GitLab user '{}'.", username),
+                })
+            })
+            .collect::<Vec<_>>();
+        Box::new(future::join_all(users))
+    }
+
+    /// Sends a request for `path` relative to the project.
+    fn request<D>(
+        &self,
+        method: hyper::Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> Box<Future<Item = D, Error = Error>>
+    where
+        D: serde::de::DeserializeOwned + 'static,
+    {
+        let url = format!("{}/projects/{}{}", self.api_url, self.project, path);
+        self.request_url(method, url, body)
+    }
+
+    fn request_url<D>(
+        &self,
+        method: hyper::Method,
+        url: String,
+        body: Option<serde_json::Value>,
+    ) -> Box<Future<Item = D, Error = Error>>
+    where
+        D: serde::de::DeserializeOwned + 'static,
+    {
+        debug!("{} {}", method, url);
+        let uri = match url.parse::<hyper::Uri>() {
+            Ok(uri) => uri,
+            Err(e) => {
+                return Box::new(future::err(Error::with_chain(
+                    e,
+                    format!("Invalid GitLab API url '{}'.", url),
+                )))
+            }
+        };
+        let mut request = hyper::Request::new(method, uri);
+        request
+            .headers_mut()
+            .set(PrivateToken(self.token.clone()));
+        if let Some(body) = body {
+            request.headers_mut().set(hyper::header::ContentType::json());
+            request.set_body(body.to_string());
+        }
+        Box::new(
+            self.http
+                .request(request)
+                .map_err(move |e| {
+                    Error::with_chain(
+                        e,
+                        ErrorKind::Transient(format!("Request to '{}' failed.", url)),
+                    )
+                })
+                .and_then(|response| {
+                    let status = response.status();
+                    response
+                        .body()
+                        .concat2()
+                        .map_err(|e| {
+                            Error::with_chain(
+                                e,
+                                ErrorKind::Transient(
+                                    "Could not read GitLab API response.".to_string(),
+                                ),
+                            )
+                        })
+                        .and_then(move |body| {
+                            if status.is_server_error() {
+                                bail!(ErrorKind::Transient(format!(
+                                    "GitLab API request failed with status '{}': {}",
+                                    status,
+                                    String::from_utf8_lossy(&body)
+                                )));
+                            }
+                            // GitLab refuses a second open merge request from a source branch.
+                            if status == hyper::StatusCode::Conflict {
+                                bail!(ErrorKind::PullRequestExists(format!(
+                                    "GitLab API request failed with status '{}': {}",
+                                    status,
+                                    String::from_utf8_lossy(&body)
+                                )));
+                            }
+                            if !status.is_success() {
+                                bail!(
+                                    "GitLab API request failed with status '{}': {}",
+                                    status,
+                                    String::from_utf8_lossy(&body)
+                                );
+                            }
+                            serde_json::from_slice::<D>(&body)
+                                .chain_err(|| "Could not parse GitLab API response.")
+                        })
+                }),
+        )
+    }
+}
+
+impl forge::Forge for Gitlab {
+    fn check_access(&self) -> Box<Future<Item = (), Error = Error>> {
+        let project = self.project.replace("%2F", "/");
+        Box::new(
+            self.request::<serde_json::Value>(hyper::Method::Get, "", None)
+                .map(|_| ())
+                .map_err(move |e| {
+                    e.context(format!(
+                        "Could not access project {}, make sure the token has the 'api' scope.",
+                        project
+                    ))
+                }),
+        )
+    }
+
+    fn default_branch(&self) -> Box<Future<Item = Option<String>, Error = Error>> {
+        let project = self.project.replace("%2F", "/");
+        Box::new(
+            self.request::<Project>(hyper::Method::Get, "", None)
+                .map(|project| project.default_branch)
+                .map_err(move |e| {
+                    e.context(format!("Could not get the default branch of {}.", project))
+                }),
+        )
+    }
+
+    fn rate_limit(&self) -> Box<Future<Item = Option<forge::RateLimit>, Error = Error>> {
+        // GitLab only reports its rate limits once a request is refused.
+        Box::new(future::ok(None))
+    }
+
+    fn get_pull_request(
+        &self,
+        number: u64,
+    ) -> Box<Future<Item = forge::PullRequest, Error = Error>> {
+        Box::new(
+            self.request::<MergeRequest>(
+                hyper::Method::Get,
+                &format!("/merge_requests/{}", number),
+                None,
+            ).map(forge::PullRequest::from)
+                .map_err(move |e| {
+                    e.context(format!("Could not get merge request !{}.", number))
+                }),
+        )
+    }
+
+    fn find_pull_request(
+        &self,
+        head: &str,
+    ) -> Box<Future<Item = Option<forge::PullRequest>, Error = Error>> {
+        // Merge requests from forks aren't supported, so an owner is dropped.
+        let source_branch = head.rsplit(':').next().unwrap_or(head).to_string();
+        Box::new(
+            self.request::<Vec<MergeRequest>>(
+                hyper::Method::Get,
+                &format!(
+                    "/merge_requests?{}",
+                    query(&[("state", "opened"), ("source_branch", &source_branch)])
+                ),
+                None,
+            ).map(|merge_requests| {
+                merge_requests
+                    .into_iter()
+                    .next()
+                    .map(forge::PullRequest::from)
+            })
+                .map_err(move |e| {
+                    e.context(format!(
+                        "Could not find merge request from '{}'.",
+                        source_branch
+                    ))
+                }),
+        )
+    }
+
+    fn find_merged_pull_request(
+        &self,
+        head: &str,
+    ) -> Box<Future<Item = Option<forge::PullRequest>, Error = Error>> {
+        let source_branch = head.rsplit(':').next().unwrap_or(head).to_string();
+        Box::new(
+            self.request::<Vec<MergeRequest>>(
+                hyper::Method::Get,
+                &format!(
+                    "/merge_requests?{}",
+                    query(&[("state", "merged"), ("source_branch", &source_branch)])
+                ),
+                None,
+            ).map(|merge_requests| {
+                merge_requests
+                    .into_iter()
+                    .next()
+                    .map(forge::PullRequest::from)
+            })
+                .map_err(move |e| {
+                    e.context(format!(
+                        "Could not find merge request from '{}'.",
+                        source_branch
+                    ))
+                }),
+        )
+    }
+
+    fn list_open_pull_requests(
+        &self,
+    ) -> Box<Future<Item = Vec<forge::PullRequest>, Error = Error>> {
+        Box::new(
+            self.request::<Vec<MergeRequest>>(
+                hyper::Method::Get,
+                "/merge_requests?state=opened&per_page=100",
+                None,
+            ).map(|merge_requests| {
+                merge_requests
+                    .into_iter()
+                    .map(forge::PullRequest::from)
+                    .collect()
+            })
+                .map_err(|e| e.context("Could not list merge requests.")),
+        )
+    }
+
+    fn create_pull_request(
+        &self,
+        pull_request: &forge::NewPullRequest,
+    ) -> Box<Future<Item = forge::PullRequest, Error = Error>> {
+        // GitLab marks work in progress merge requests with a title prefix.
+        let title = if pull_request.draft {
+            format!("WIP: {}", pull_request.title)
+        } else {
+            pull_request.title.to_string()
+        };
+        Box::new(
+            self.request::<MergeRequest>(
+                hyper::Method::Post,
+                "/merge_requests",
+                Some(json!({
+                    "source_branch": pull_request.head,
+                    "target_branch": pull_request.base,
+                    "title": title,
+                    "description": pull_request.body,
+                })),
+            ).map(forge::PullRequest::from)
+                .map_err(|e| e.context("Could not create merge request.")),
+        )
+    }
+
+    fn update_pull_request(
+        &self,
+        number: u64,
+        title: &str,
+        body: Option<&str>,
+    ) -> Box<Future<Item = forge::PullRequest, Error = Error>> {
+        let mut options = json!({ "title": title });
+        if let Some(body) = body {
+            options["description"] = json!(body);
+        }
+        Box::new(
+            self.request::<MergeRequest>(
+                hyper::Method::Put,
+                &format!("/merge_requests/{}", number),
+                Some(options),
+            ).map(forge::PullRequest::from)
+                .map_err(move |e| {
+                    e.context(format!("Could not update merge request !{}.", number))
+                }),
+        )
+    }
+
+    fn update_pull_request_base(
+        &self,
+        number: u64,
+        base: &str,
+    ) -> Box<Future<Item = forge::PullRequest, Error = Error>> {
+        Box::new(
+            self.request::<MergeRequest>(
+                hyper::Method::Put,
+                &format!("/merge_requests/{}", number),
+                Some(json!({ "target_branch": base })),
+            ).map(forge::PullRequest::from)
+                .map_err(move |e| {
+                    e.context(format!("Could not change the target of merge request !{}.", number))
+                }),
+        )
+    }
+
+    fn request_reviewers(
+        &self,
+        number: u64,
+        reviewers: &[String],
+        team_reviewers: &[String],
+    ) -> Box<Future<Item = (), Error = Error>> {
+        // GitLab has no teams, a group's members would have to be requested one by one.
+        if !team_reviewers.is_empty() {
+            warn!(
+                "GitLab merge requests can't have team reviewers, not requesting {}.",
+                team_reviewers.join(", ")
+            );
+        }
+        if reviewers.is_empty() {
+            return Box::new(future::ok(()));
+        }
+        let gitlab = self.clone();
+        Box::new(
+            self.user_ids(reviewers)
+                .and_then(move |ids| {
+                    gitlab.request::<serde_json::Value>(
+                        hyper::Method::Put,
+                        &format!("/merge_requests/{}", number),
+                        Some(json!({ "reviewer_ids": ids })),
+                    )
+                })
+                .map(|_| ())
+                .map_err(move |e| {
+                    e.context(format!("Could not request reviewers for merge request !{}.", number))
+                }),
+        )
+    }
+
+    fn add_assignees(
+        &self,
+        number: u64,
+        assignees: &[String],
+    ) -> Box<Future<Item = (), Error = Error>> {
+        let gitlab = self.clone();
+        Box::new(
+            self.user_ids(assignees)
+                .and_then(move |ids| {
+                    gitlab.request::<serde_json::Value>(
+                        hyper::Method::Put,
+                        &format!("/merge_requests/{}", number),
+                        Some(json!({ "assignee_ids": ids })),
+                    )
+                })
+                .map(|_| ())
+                .map_err(move |e| {
+                    e.context(format!("Could not assign merge request !{}.", number))
+                }),
+        )
+    }
+
+    fn set_labels(&self, number: u64, labels: &[String]) -> Box<Future<Item = (), Error = Error>> {
+        Box::new(
+            self.request::<serde_json::Value>(
+                hyper::Method::Put,
+                &format!("/merge_requests/{}", number),
+                Some(json!({ "labels": labels.join(",") })),
+            ).map(|_| ())
+                .map_err(move |e| {
+                    e.context(format!("Could not set labels on merge request !{}.", number))
+                }),
+        )
+    }
+
+    fn set_milestone(&self, number: u64, milestone: &str) -> Box<Future<Item = (), Error = Error>> {
+        // Merge requests are given milestones by id, so the title has to be looked up first.
+        let title = milestone.to_string();
+        let project = self.project.replace("%2F", "/");
+        let gitlab = self.clone();
+        Box::new(
+            self.request::<Vec<Milestone>>(
+                hyper::Method::Get,
+                &format!("/milestones?{}", query(&[("title", &title)])),
+                None,
+            ).and_then(move |milestones| match milestones.into_iter().next() {
+                Some(milestone) => Ok(milestone.id),
+                None => bail!("Milestone '{}' does not exist on {}.", title, project),
+            })
+                .and_then(move |id| {
+                    gitlab.request::<serde_json::Value>(
+                        hyper::Method::Put,
+                        &format!("/merge_requests/{}", number),
+                        Some(json!({ "milestone_id": id })),
+                    )
+                })
+                .map(|_| ())
+                .map_err(move |e| {
+                    e.context(format!("Could not set the milestone of merge request !{}.", number))
+                }),
+        )
+    }
+
+    fn close_pull_request(&self, number: u64) -> Box<Future<Item = (), Error = Error>> {
+        Box::new(
+            self.request::<serde_json::Value>(
+                hyper::Method::Put,
+                &format!("/merge_requests/{}", number),
+                Some(json!({ "state_event": "close" })),
+            ).map(|_| ())
+                .map_err(move |e| e.context(format!("Could not close merge request !{}.", number))),
+        )
+    }
+
+    fn merge_pull_request(
+        &self,
+        number: u64,
+        sha: &str,
+        method: forge::MergeMethod,
+    ) -> Box<Future<Item = (), Error = Error>> {
+        // Whether merge requests are rebased is a project setting.
+        if method == forge::MergeMethod::Rebase {
+            return Box::new(future::err(
+                "GitLab merge requests can only be merged or squashed, the project settings \
+                 decide whether they're rebased."
+                    .into(),
+            ));
+        }
+        Box::new(
+            self.request::<serde_json::Value>(
+                hyper::Method::Put,
+                &format!("/merge_requests/{}/merge", number),
+                Some(json!({ "sha": sha, "squash": method == forge::MergeMethod::Squash })),
+            ).map(|_| ())
+                .map_err(move |e| e.context(format!("Could not merge merge request !{}.", number))),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use forge::Forge;
+
+    #[test]
+    fn query_can_encode_special_characters() {
+        assert_eq!(
+            query(&[("source_branch", "feature/a&b"), ("title", "v1.0 #2+")]),
+            "source_branch=feature%2Fa%26b&title=v1.0+%232%2B"
+        );
+    }
+
+    #[test]
+    fn request_reviewers_skips_team_reviewers() {
+        let core = tokio_core::reactor::Core::new().unwrap();
+        let gitlab = Gitlab::new("gitlab.com", "Coneko", "stack", "token", &core.handle()).unwrap();
+        let result = gitlab
+            .request_reviewers(1, &[], &["Coneko/reviewers".to_string()])
+            .wait();
+        assert!(result.is_ok());
+    }
+}
@@ -1,9 +1,34 @@
 #[macro_use]
 extern crate error_chain;
+extern crate futures;
+extern crate git2;
+extern crate hubcaps;
+#[macro_use]
+extern crate hyper;
+extern crate hyper_tls;
 #[macro_use]
 extern crate indoc;
+extern crate libgit2_sys;
+#[macro_use]
+extern crate log;
 extern crate regex;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+#[macro_use]
+extern crate serde_json;
 extern crate tempfile;
+extern crate tokio_core;
+extern crate toml;
+extern crate url;
 
+pub mod branch;
 pub mod changeset;
+pub mod codeowners;
+pub mod config;
 pub mod errors;
+pub mod forge;
+pub mod git;
+pub mod github;
+pub mod gitlab;
+pub mod state;
@@ -0,0 +1,218 @@
+use errors::*;
+use git;
+use git2;
+use std;
+use std::fmt;
+use std::io::{Read, Write};
+
+/// A commit uploaded by `up` and the number of its pull request.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub commit: git2::Oid,
+    pub pr_number: u64,
+}
+
+/// The commits of the stack in the order they were uploaded, saved in the git directory so that
+/// the other commands know which commits form the stack without asking the forge.
+#[derive(Debug, Default, PartialEq)]
+pub struct StackState {
+    pub entries: Vec<Entry>,
+}
+
+impl StackState {
+    const FILE_NAME: &'static str = "stack";
+    const LOCK_FILE_NAME: &'static str = "stack.lock";
+
+    /// Shared by the worktrees of the repository, like the commits are.
+    fn path(repo: &git2::Repository) -> std::path::PathBuf {
+        git::common_dir(repo).join(Self::FILE_NAME)
+    }
+
+    /// Loads the state of the repository, a missing file is the same as an empty one.
+    pub fn load(repo: &git2::Repository) -> Result<StackState> {
+        let path = Self::path(repo);
+        let mut file = match std::fs::File::open(&path) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(StackState::default())
+            }
+            Err(e) => {
+                return Err(e).chain_err(|| format!("Could not open '{}'.", path.display()))
+            }
+        };
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)
+            .chain_err(|| format!("Could not read '{}'.", path.display()))?;
+        Self::new_from_string(&contents).chain_err(|| format!("Invalid '{}'.", path.display()))
+    }
+
+    /// Loads the state, lets `update` change it and saves it back. The state is locked in the
+    /// meantime like git locks its refs, so two commands running at once can't lose each other's
+    /// changes.
+    pub fn update<F>(repo: &git2::Repository, update: F) -> Result<()>
+    where
+        F: FnOnce(&mut StackState),
+    {
+        let lock_path = git::common_dir(repo).join(Self::LOCK_FILE_NAME);
+        let mut lock = match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&lock_path)
+        {
+            Ok(lock) => lock,
+            Err(ref e) if e.kind() == std::io::ErrorKind::AlreadyExists => bail!(
+                "'{}' exists, another stack command is running. If none is, remove the file.",
+                lock_path.display()
+            ),
+            Err(e) => {
+                return Err(e).chain_err(|| format!("Could not create '{}'.", lock_path.display()))
+            }
+        };
+        // The lock file becomes the new state, so readers never see a partly written one.
+        let result = Self::load(repo).and_then(|mut state| {
+            update(&mut state);
+            lock.write_all(state.to_string().as_bytes())
+                .and_then(|_| lock.sync_all())
+                .chain_err(|| format!("Could not write '{}'.", lock_path.display()))?;
+            std::fs::rename(&lock_path, Self::path(repo))
+                .chain_err(|| format!("Could not replace '{}'.", Self::path(repo).display()))
+        });
+        if result.is_err() {
+            let _ = std::fs::remove_file(&lock_path);
+        }
+        result
+    }
+
+    pub fn new_from_string(string: &str) -> Result<StackState> {
+        let mut entries = Vec::new();
+        for line in string.lines().filter(|line| !line.trim().is_empty()) {
+            let mut words = line.split_whitespace();
+            let (commit, pr_number) = match (words.next(), words.next(), words.next()) {
+                (Some(commit), Some(pr_number), None) => (commit, pr_number),
+                _ => bail!("Invalid line '{}'.", line),
+            };
+            entries.push(Entry {
+                commit: git2::Oid::from_str(commit)
+                    .chain_err(|| format!("Invalid commit id '{}'.", commit))?,
+                pr_number: pr_number
+                    .parse()
+                    .chain_err(|| format!("Invalid pull request number '{}'.", pr_number))?,
+            });
+        }
+        Ok(StackState { entries })
+    }
+
+    /// Records that `commit` was uploaded to pull request `pr_number`. An amended commit replaces
+    /// the one its pull request was uploaded with before, keeping its place in the stack.
+    pub fn record(&mut self, commit: git2::Oid, pr_number: u64) {
+        let entry = Entry { commit, pr_number };
+        match self
+            .entries
+            .iter()
+            .position(|entry| entry.pr_number == pr_number || entry.commit == commit)
+        {
+            Some(index) => self.entries[index] = entry,
+            None => self.entries.push(entry),
+        }
+    }
+
+    /// Drops the entries of commits that no longer exist, like those of a stack that was rebased
+    /// and garbage collected since.
+    pub fn prune(&mut self, repo: &git2::Repository) {
+        self.entries.retain(|entry| repo.find_commit(entry.commit).is_ok());
+    }
+}
+
+impl fmt::Display for StackState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{} {}", entry.commit, entry.pr_number)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIRST_COMMIT: &str = "3e265444a803eac928b02a735564d0a544759215";
+    const SECOND_COMMIT: &str = "7c2add7be6815c72f294e84a8596739132fe9f86";
+
+    fn oid(id: &str) -> git2::Oid {
+        git2::Oid::from_str(id).unwrap()
+    }
+
+    #[test]
+    fn new_from_string_can_parse_empty_string() {
+        let result = StackState::new_from_string("");
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), StackState::default());
+    }
+
+    #[test]
+    fn new_from_string_can_parse_entries() {
+        let result = StackState::new_from_string(&format!(
+            "{} 1\n{} 2\n",
+            FIRST_COMMIT, SECOND_COMMIT
+        ));
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap().entries,
+            vec![
+                Entry {
+                    commit: oid(FIRST_COMMIT),
+                    pr_number: 1,
+                },
+                Entry {
+                    commit: oid(SECOND_COMMIT),
+                    pr_number: 2,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn new_from_string_cannot_parse_invalid_commit() {
+        let result = StackState::new_from_string("nope 1\n");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn new_from_string_cannot_parse_line_without_pr_number() {
+        let result = StackState::new_from_string(&format!("{}\n", FIRST_COMMIT));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn display_can_round_trip() {
+        let mut state = StackState::default();
+        state.record(oid(FIRST_COMMIT), 1);
+        state.record(oid(SECOND_COMMIT), 2);
+        let result = StackState::new_from_string(&state.to_string());
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), state);
+    }
+
+    #[test]
+    fn record_can_replace_amended_commit() {
+        let mut state = StackState::default();
+        state.record(oid(FIRST_COMMIT), 1);
+        state.record(oid(SECOND_COMMIT), 2);
+        state.record(oid(FIRST_COMMIT), 3);
+        state.record(oid("1111111111111111111111111111111111111111"), 2);
+        assert_eq!(
+            state.entries,
+            vec![
+                Entry {
+                    commit: oid(FIRST_COMMIT),
+                    pr_number: 3,
+                },
+                Entry {
+                    commit: oid("1111111111111111111111111111111111111111"),
+                    pr_number: 2,
+                },
+            ]
+        );
+    }
+}
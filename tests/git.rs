@@ -0,0 +1,256 @@
+extern crate git2;
+extern crate stack;
+
+use stack::git;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static FIXTURE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// A working repository with a bare repository as its `origin`, both removed once dropped.
+struct Fixture {
+    dir: std::path::PathBuf,
+    origin: git2::Repository,
+    repo: git2::Repository,
+}
+
+impl Fixture {
+    fn new() -> Fixture {
+        let dir = std::env::temp_dir().join(format!(
+            "stack-test-{}-{}",
+            std::process::id(),
+            FIXTURE_COUNT.fetch_add(1, Ordering::SeqCst)
+        ));
+        let origin = git2::Repository::init_bare(dir.join("origin.git")).unwrap();
+        let repo = git2::Repository::init(dir.join("repo")).unwrap();
+        repo.remote("origin", origin.path().to_str().unwrap()).unwrap();
+        Fixture { dir, origin, repo }
+    }
+
+    /// Commits an empty tree on top of `parent`, without moving any branch.
+    fn commit(&self, parent: Option<git2::Oid>, message: &str) -> git2::Oid {
+        let signature = git2::Signature::now("Alice", "alice@example.com").unwrap();
+        let tree_id = self.repo.treebuilder(None).unwrap().write().unwrap();
+        let tree = self.repo.find_tree(tree_id).unwrap();
+        let parents = parent
+            .map(|id| vec![self.repo.find_commit(id).unwrap()])
+            .unwrap_or_default();
+        let parents = parents.iter().collect::<Vec<_>>();
+        self.repo
+            .commit(None, &signature, &signature, message, &tree, &parents)
+            .unwrap()
+    }
+
+    fn push(&self, refspec: &str) {
+        let mut remote = self.repo.find_remote("origin").unwrap();
+        remote.push(&[refspec], None).unwrap();
+    }
+
+    fn fetch(&self) {
+        let mut remote = self.repo.find_remote("origin").unwrap();
+        remote
+            .fetch(&["+refs/heads/*:refs/remotes/origin/*"], None, None)
+            .unwrap();
+    }
+
+    /// Adds a worktree at `commit` the way `git worktree add --detach` does.
+    fn add_worktree(&self, name: &str, commit: git2::Oid) -> std::path::PathBuf {
+        let workdir = self.dir.join(name);
+        let gitdir = self.repo.path().join("worktrees").join(name);
+        std::fs::create_dir_all(&workdir).unwrap();
+        std::fs::create_dir_all(&gitdir).unwrap();
+        let write = |path: std::path::PathBuf, contents: String| {
+            std::fs::write(path, contents).unwrap();
+        };
+        write(workdir.join(".git"), format!("gitdir: {}\n", gitdir.display()));
+        write(gitdir.join("gitdir"), format!("{}\n", workdir.join(".git").display()));
+        write(gitdir.join("commondir"), "../..\n".to_string());
+        write(gitdir.join("HEAD"), format!("{}\n", commit));
+        workdir
+    }
+
+    fn remote_branches(&self) -> std::collections::HashMap<String, git2::Oid> {
+        let mut remote = self.repo.find_remote("origin").unwrap();
+        git::remote_branches(&mut remote, git2::RemoteCallbacks::new()).unwrap()
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[test]
+fn stack_commits_can_list_commits_oldest_first() {
+    let fixture = Fixture::new();
+    let base = fixture.commit(None, "base");
+    fixture.repo.reference("refs/heads/main", base, true, "").unwrap();
+    let first = fixture.commit(Some(base), "first");
+    let second = fixture.commit(Some(first), "second");
+    let head = fixture.repo.find_commit(second).unwrap();
+    let result = git::stack_commits(&fixture.repo, "main", &head);
+    assert!(result.is_ok());
+    let ids = result.unwrap().iter().map(|commit| commit.id()).collect::<Vec<_>>();
+    assert_eq!(ids, vec![first, second]);
+}
+
+#[test]
+fn stack_commits_cannot_list_empty_stack() {
+    let fixture = Fixture::new();
+    let base = fixture.commit(None, "base");
+    fixture.repo.reference("refs/heads/main", base, true, "").unwrap();
+    let head = fixture.repo.find_commit(base).unwrap();
+    assert!(git::stack_commits(&fixture.repo, "main", &head).is_err());
+}
+
+#[test]
+fn branch_refspec_can_push_new_branch() {
+    let fixture = Fixture::new();
+    let id = fixture.commit(None, "first");
+    let commit = fixture.repo.find_commit(id).unwrap();
+    let result = git::branch_refspec(&fixture.repo, "alice-first-pr", &commit, false);
+    assert!(result.is_ok());
+    let refspec = result.unwrap();
+    assert_eq!(refspec, "refs/heads/alice-first-pr");
+    fixture.push(&refspec);
+    let pushed = fixture.origin.find_branch("alice-first-pr", git2::BranchType::Local);
+    assert_eq!(pushed.unwrap().get().target(), Some(id));
+    assert_eq!(fixture.remote_branches().get("alice-first-pr"), Some(&id));
+}
+
+#[test]
+fn branch_refspec_cannot_move_branch_without_overwrite() {
+    let fixture = Fixture::new();
+    let first = fixture.repo.find_commit(fixture.commit(None, "first")).unwrap();
+    let other = fixture.repo.find_commit(fixture.commit(None, "other")).unwrap();
+    fixture.push(&git::branch_refspec(&fixture.repo, "custom", &first, false).unwrap());
+    assert!(git::branch_refspec(&fixture.repo, "custom", &other, false).is_err());
+    let result = git::branch_refspec(&fixture.repo, "custom", &other, true);
+    assert!(result.is_ok());
+    let refspec = result.unwrap();
+    assert_eq!(refspec, "+refs/heads/custom");
+    // The commits are unrelated, so only a forced push moves the remote branch.
+    fixture.push(&refspec);
+    assert_eq!(fixture.remote_branches().get("custom"), Some(&other.id()));
+}
+
+#[test]
+fn remote_branches_at_can_find_default_branch_first() {
+    let fixture = Fixture::new();
+    let base = fixture.repo.find_commit(fixture.commit(None, "base")).unwrap();
+    fixture.push(&git::branch_refspec(&fixture.repo, "release", &base, false).unwrap());
+    fixture.push(&git::branch_refspec(&fixture.repo, "main", &base, false).unwrap());
+    fixture.fetch();
+    assert_eq!(
+        git::default_branch(&fixture.repo, "origin"),
+        Some(("origin/main".to_string(), base.id()))
+    );
+    assert_eq!(
+        git::remote_branches_at(&fixture.repo, "origin", base.id()),
+        vec!["main".to_string(), "release".to_string()]
+    );
+}
+
+#[test]
+fn default_branch_can_follow_remote_head() {
+    let fixture = Fixture::new();
+    let base = fixture.repo.find_commit(fixture.commit(None, "base")).unwrap();
+    fixture.push(&git::branch_refspec(&fixture.repo, "main", &base, false).unwrap());
+    fixture.push(&git::branch_refspec(&fixture.repo, "trunk", &base, false).unwrap());
+    fixture.fetch();
+    assert_eq!(git::remote_head(&fixture.repo, "origin"), None);
+    fixture
+        .repo
+        .reference_symbolic("refs/remotes/origin/HEAD", "refs/remotes/origin/trunk", true, "")
+        .unwrap();
+    assert_eq!(git::remote_head(&fixture.repo, "origin"), Some("trunk".to_string()));
+    assert_eq!(
+        git::default_branch(&fixture.repo, "origin"),
+        Some(("origin/trunk".to_string(), base.id()))
+    );
+}
+
+#[test]
+fn ssh_command_can_parse_identity_files() {
+    let result = git::SshCommand::parse(
+        "/usr/bin/ssh -i ~/.ssh/work -o 'IdentityFile=/home/alice/my keys/id' -i~/.ssh/other",
+    );
+    assert_eq!(
+        result,
+        Some(git::SshCommand {
+            identity_files: vec![
+                "~/.ssh/work".to_string(),
+                "/home/alice/my keys/id".to_string(),
+                "~/.ssh/other".to_string(),
+            ],
+            ignored_args: vec![],
+        })
+    );
+}
+
+#[test]
+fn ssh_command_can_list_ignored_args() {
+    let result = git::SshCommand::parse("ssh -p 2222 -i key -o StrictHostKeyChecking=no");
+    assert!(result.is_some());
+    let ssh_command = result.unwrap();
+    assert_eq!(ssh_command.identity_files, vec!["key".to_string()]);
+    assert_eq!(
+        ssh_command.ignored_args,
+        vec!["-p", "2222", "-o", "StrictHostKeyChecking=no"]
+    );
+}
+
+#[test]
+fn ssh_command_cannot_parse_other_program() {
+    assert_eq!(git::SshCommand::parse("/usr/local/bin/ssh-wrapper -i key"), None);
+    assert_eq!(git::SshCommand::parse("ssh -i 'key"), None);
+}
+
+#[test]
+fn discover_can_find_repo_from_nested_path() {
+    let fixture = Fixture::new();
+    let nested = fixture.dir.join("repo").join("src").join("module");
+    std::fs::create_dir_all(&nested).unwrap();
+    let result = git::discover(&nested);
+    assert!(result.is_ok());
+    let repo = result.unwrap();
+    assert_eq!(
+        repo.workdir().unwrap().canonicalize().unwrap(),
+        fixture.dir.join("repo").canonicalize().unwrap()
+    );
+    assert!(!repo.is_worktree());
+    assert_eq!(git::common_dir(&repo), repo.path());
+    assert_eq!(git::superproject(&repo), None);
+}
+
+#[test]
+fn discover_can_open_worktree_with_main_config() {
+    let fixture = Fixture::new();
+    let base = fixture.commit(None, "base");
+    let workdir = fixture.add_worktree("feature", base);
+    fixture.repo.config().unwrap().set_str("stack.prTrailer", "PR").unwrap();
+    let nested = workdir.join("docs");
+    std::fs::create_dir_all(&nested).unwrap();
+    let result = git::discover(&nested);
+    assert!(result.is_ok());
+    let repo = result.unwrap();
+    assert!(repo.is_worktree());
+    assert_eq!(repo.head().unwrap().target(), Some(base));
+    assert_eq!(
+        git::common_dir(&repo).canonicalize().unwrap(),
+        fixture.repo.path().canonicalize().unwrap()
+    );
+    // Credential helpers and the other settings come from the main repository's config.
+    let config = repo.config().unwrap();
+    assert_eq!(config.get_string("stack.prTrailer").unwrap(), "PR");
+    assert!(repo.find_remote("origin").is_ok());
+}
+
+#[test]
+fn discover_cannot_open_worktree_of_missing_repo() {
+    let fixture = Fixture::new();
+    let base = fixture.commit(None, "base");
+    let workdir = fixture.add_worktree("feature", base);
+    std::fs::remove_file(fixture.repo.path().join("config")).unwrap();
+    assert!(git::discover(&workdir).is_err());
+}